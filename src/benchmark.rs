@@ -0,0 +1,183 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Benchmark mode (--benchmark). Each URL is loaded in turn, and once
+//! the load ended and the page settled, its timings are recorded. The
+//! report is written as CSV, or JSON if the report file ends in .json.
+
+use perf::millis;
+use serde_json;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+#[derive(Serialize)]
+pub struct BenchmarkResult {
+    pub url: String,
+    pub run: u64,
+    pub head_parsed_ms: Option<u64>,
+    pub load_end_ms: Option<u64>,
+    // From the session history, after redirects
+    pub final_url: Option<String>,
+    pub title: Option<String>,
+    pub error: Option<String>,
+}
+
+struct Measure {
+    url: String,
+    run: u64,
+    start: Instant,
+    head_parsed: Option<Duration>,
+    load_end: Option<Duration>,
+}
+
+pub struct Benchmark {
+    urls: Vec<String>,
+    runs: u64,
+    pub settle: Duration,
+    // Loads started so far, all runs included
+    started: usize,
+    current: Option<Measure>,
+    results: Vec<BenchmarkResult>,
+}
+
+/// One URL per line. Empty lines and lines starting with # are ignored.
+pub fn read_urls(path: &Path) -> io::Result<Vec<String>> {
+    let file = File::open(path)?;
+    let mut urls = vec![];
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() && !line.starts_with('#') {
+            urls.push(line.to_owned());
+        }
+    }
+    Ok(urls)
+}
+
+impl Benchmark {
+    pub fn new(urls: Vec<String>, runs: u64, settle: Duration) -> Benchmark {
+        Benchmark {
+            urls,
+            runs,
+            settle,
+            started: 0,
+            current: None,
+            results: vec![],
+        }
+    }
+
+    /// The next URL to load. None once all the runs are done. Each run
+    /// loads all the URLs.
+    pub fn start_next(&mut self) -> Option<String> {
+        if self.started == self.urls.len() * self.runs as usize {
+            return None;
+        }
+        let url = self.urls[self.started % self.urls.len()].clone();
+        let run = (self.started / self.urls.len()) as u64 + 1;
+        info!("Benchmark: loading {} (run {}/{})", url, run, self.runs);
+        self.started += 1;
+        self.current = Some(Measure {
+            url: url.clone(),
+            run,
+            start: Instant::now(),
+            head_parsed: None,
+            load_end: None,
+        });
+        Some(url)
+    }
+
+    pub fn head_parsed(&mut self) {
+        if let Some(ref mut measure) = self.current {
+            if measure.head_parsed.is_none() {
+                measure.head_parsed = Some(measure.start.elapsed());
+            }
+        }
+    }
+
+    /// Returns true if this is the end of the load being measured. The
+    /// page is then given some time to settle.
+    pub fn load_end(&mut self) -> bool {
+        if let Some(ref mut measure) = self.current {
+            if measure.load_end.is_none() {
+                measure.load_end = Some(measure.start.elapsed());
+                return true;
+            }
+        }
+        false
+    }
+
+    /// The current page settled, or failed.
+    pub fn finish(&mut self, final_url: Option<String>, title: Option<String>, error: Option<String>) {
+        if let Some(measure) = self.current.take() {
+            let error = error.or_else(|| {
+                if measure.load_end.is_none() { Some("Load didn't end".to_owned()) } else { None }
+            });
+            if let Some(ref error) = error {
+                warn!("Benchmark: {} failed: {}", measure.url, error);
+            }
+            self.results.push(BenchmarkResult {
+                url: measure.url,
+                run: measure.run,
+                head_parsed_ms: measure.head_parsed.map(millis),
+                load_end_ms: measure.load_end.map(millis),
+                final_url,
+                title,
+                error,
+            });
+        }
+    }
+
+    pub fn has_failures(&self) -> bool {
+        self.results.iter().any(|result| result.error.is_some())
+    }
+
+    /// To `stdout` if no path is given.
+    pub fn write_report(&self, path: Option<&Path>, stdout: Box<Write>) -> io::Result<()> {
+        match path {
+            Some(path) => {
+                let writer = BufWriter::new(File::create(path)?);
+                if path.extension().map_or(false, |ext| ext == "json") {
+                    self.write_json(writer)
+                } else {
+                    self.write_csv(writer)
+                }
+            }
+            None => self.write_csv(stdout),
+        }
+    }
+
+    fn write_json<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut writer, &self.results).map_err(|err| {
+            io::Error::new(io::ErrorKind::Other, err)
+        })?;
+        writer.flush()
+    }
+
+    fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "url,run,head_parsed_ms,load_end_ms,final_url,title,error")?;
+        for result in &self.results {
+            let number = |value: Option<u64>| value.map_or(String::new(), |v| v.to_string());
+            let text = |value: &Option<String>| value.as_ref().map_or(String::new(), |v| csv_field(v));
+            writeln!(writer, "{},{},{},{},{},{},{}",
+                     csv_field(&result.url),
+                     result.run,
+                     number(result.head_parsed_ms),
+                     number(result.load_end_ms),
+                     text(&result.final_url),
+                     text(&result.title),
+                     text(&result.error))?;
+        }
+        writer.flush()
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
@@ -0,0 +1,288 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use getopts::{Matches, Options};
+use kiosk;
+use log::LogLevelFilter;
+use profile::Profile;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+const DEFAULT_WINDOW_SIZE: (u32, u32) = (1024, 768);
+const DEFAULT_BENCHMARK_RUNS: u64 = 1;
+// In milliseconds
+const DEFAULT_BENCHMARK_SETTLE: u64 = 1000;
+
+pub struct ShellOptions {
+    pub help: bool,
+    pub version: bool,
+    pub resources_path: Option<PathBuf>,
+    pub window_size: (u32, u32),
+    pub log_level: LogLevelFilter,
+    pub profile: Option<String>,
+    pub private: bool,
+    pub user_agent: Option<String>,
+    pub new_window: bool,
+    pub webdriver_port: Option<u16>,
+    pub filter_lists: Vec<PathBuf>,
+    pub kiosk: bool,
+    pub kiosk_allow: Vec<String>,
+    pub kiosk_commands: Vec<String>,
+    pub kiosk_idle: Option<u64>,
+    pub kiosk_rotate: Option<u64>,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub benchmark: Option<PathBuf>,
+    pub benchmark_runs: u64,
+    pub benchmark_settle: u64,
+    pub benchmark_report: Option<PathBuf>,
+    pub servo_args: Vec<String>,
+    pub urls: Vec<String>,
+}
+
+fn options() -> Options {
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "Print this message and exit");
+    opts.optflag("", "version", "Print the version and exit");
+    opts.optopt("", "resources-path", "Path to Servo's resources directory", "PATH");
+    opts.optopt("", "window-size", "Initial window size (default: 1024x768)", "WxH");
+    opts.optopt("", "log-level", "One of off, error, warn, info, debug, trace (default: info)", "LEVEL");
+    opts.optopt("", "profile", "Profile name, or directory, where cookies, local storage, HSTS… are stored", "NAME|DIR");
+    opts.optflag("", "private", "Use a temporary profile, deleted on exit");
+    opts.optopt("", "user-agent", "Override the user agent string", "UA");
+    opts.optflag("", "new-window", "Start a new browser instead of opening the URLs in the running one");
+    opts.optmulti("", "filter-list", "Block navigations matching the rules of this file \
+                                      (hosts file or Adblock syntax)", "FILE");
+    opts.optflag("", "kiosk", "Fullscreen, without chrome, and restricted to the URLs given on the command line");
+    opts.optmulti("", "kiosk-allow", "Also allow navigating to this host in kiosk mode", "HOST");
+    opts.optopt("", "kiosk-commands", &format!("Commands enabled in kiosk mode, separated by commas. \
+                                                Available: {}", kiosk::COMMAND_NAMES.join(", ")), "LIST");
+    opts.optopt("", "kiosk-idle", "Go back to the first URL after this many idle minutes", "MINUTES");
+    opts.optopt("", "kiosk-rotate", "Load the next URL every SECONDS seconds when idle", "SECONDS");
+    opts.optopt("", "record", "Save the input events of the session to this file", "FILE");
+    opts.optopt("", "replay", "Replay the input events saved with --record", "FILE");
+    opts.optopt("", "benchmark", "Load each URL of this file (one per line), write a report and exit. \
+                                  The exit code is 1 if a load failed. Uses a temporary profile, \
+                                  unless --profile is given", "FILE");
+    opts.optopt("", "runs", "With --benchmark, how many times each URL is loaded (default: 1)", "N");
+    opts.optopt("", "settle", "With --benchmark, how long to wait after a load ended, \
+                              0 for none (default: 1000)", "MS");
+    opts.optopt("", "report", "With --benchmark, where to write the report, as JSON if FILE ends \
+                               with .json, CSV otherwise (default: CSV on stdout)", "FILE");
+    opts.optopt("", "webdriver", "Start Servo's WebDriver server on this port", "PORT");
+    opts.optmulti("", "servo-args", "Options passed to Servo, like \"--multiprocess --devtools 6000\". \
+                                     Quote arguments containing spaces like in a shell. \
+                                     Use --servo-args --help to list them", "ARGS");
+    opts
+}
+
+pub fn usage(program: &str) -> String {
+    let brief = format!("Usage: {} [options] [URL...]\n\nEach URL is opened in its own tab.", program);
+    options().usage(&brief)
+}
+
+fn parse_window_size(size: &str) -> Result<(u32, u32), String> {
+    let error = || format!("Invalid window size: '{}'. Expected WIDTHxHEIGHT, like 1024x768.", size);
+    let mut dimensions = size.splitn(2, 'x');
+    match (dimensions.next(), dimensions.next()) {
+        (Some(w), Some(h)) => {
+            match (u32::from_str(w), u32::from_str(h)) {
+                (Ok(w), Ok(h)) if w > 0 && h > 0 => Ok((w, h)),
+                _ => Err(error()),
+            }
+        }
+        _ => Err(error()),
+    }
+}
+
+impl ShellOptions {
+    /// Servo's command line arguments. Shell options are translated to
+    /// their Servo equivalent, followed by the --servo-args arguments.
+    pub fn servo_args(&self, profile: &Profile) -> Vec<String> {
+        let mut args = vec![];
+        // Where cookies, local storage, HSTS list… are stored
+        args.push("--config-dir".to_owned());
+        args.push(profile.servo_dir().to_string_lossy().into_owned());
+        if let Some(ref user_agent) = self.user_agent {
+            args.push("--user-agent".to_owned());
+            args.push(user_agent.clone());
+        }
+        if let Some(port) = self.webdriver_port {
+            args.push("--webdriver".to_owned());
+            args.push(port.to_string());
+        }
+        args.extend(self.servo_args.iter().cloned());
+        args
+    }
+}
+
+/// In multiprocess mode, Servo spawns content processes by running this
+/// executable again with `--content-process TOKEN` as first arguments. The
+/// option anywhere else, like in `--servo-args`, isn't a content process.
+pub fn content_process_token(args: &[String]) -> Option<String> {
+    match args.first() {
+        Some(arg) if arg == "--content-process" => args.get(1).cloned(),
+        _ => None,
+    }
+}
+
+/// Split a string into arguments like a POSIX shell does, without
+/// expansions: `--user-agent 'My UA' --profile-dir "a b"`.
+fn split_args(args: &str) -> Result<Vec<String>, String> {
+    let mut result = vec![];
+    let mut current: Option<String> = None;
+    let mut chars = args.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                let arg = current.get_or_insert(String::new());
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err(format!("Unterminated quote in --servo-args: {}", args)),
+                    }
+                }
+            }
+            '"' => {
+                let arg = current.get_or_insert(String::new());
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) if c == '"' || c == '\\' => arg.push(c),
+                            Some(c) => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                            None => return Err(format!("Unterminated quote in --servo-args: {}", args)),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err(format!("Unterminated quote in --servo-args: {}", args)),
+                    }
+                }
+            }
+            '\\' => {
+                if let Some(c) = chars.next() {
+                    current.get_or_insert(String::new()).push(c);
+                }
+            }
+            c if c.is_whitespace() => {
+                if let Some(arg) = current.take() {
+                    result.push(arg);
+                }
+            }
+            c => current.get_or_insert(String::new()).push(c),
+        }
+    }
+    if let Some(arg) = current {
+        result.push(arg);
+    }
+    Ok(result)
+}
+
+fn parse_number(matches: &Matches, name: &str) -> Result<Option<u64>, String> {
+    match matches.opt_str(name) {
+        Some(value) => match u64::from_str(&value) {
+            Ok(value) => Ok(Some(value)),
+            Err(_) => Err(format!("Invalid value for --{}: '{}'", name, value)),
+        },
+        None => Ok(None),
+    }
+}
+
+fn parse_positive(matches: &Matches, name: &str) -> Result<Option<u64>, String> {
+    match parse_number(matches, name)? {
+        Some(0) => Err(format!("Invalid value for --{}: '0'", name)),
+        value => Ok(value),
+    }
+}
+
+/// Parse command line arguments, excluding the executable name.
+pub fn parse(args: &[String]) -> Result<ShellOptions, String> {
+    let matches = options().parse(args).map_err(|f| f.to_string())?;
+
+    let window_size = match matches.opt_str("window-size") {
+        Some(size) => parse_window_size(&size)?,
+        None => DEFAULT_WINDOW_SIZE,
+    };
+
+    let log_level = match matches.opt_str("log-level") {
+        Some(level) => LogLevelFilter::from_str(&level).map_err(|_| {
+            format!("Invalid log level: '{}'", level)
+        })?,
+        None => LogLevelFilter::Info,
+    };
+
+    let webdriver_port = match matches.opt_str("webdriver") {
+        Some(port) => Some(u16::from_str(&port).map_err(|_| {
+            format!("Invalid WebDriver port: '{}'", port)
+        })?),
+        None => None,
+    };
+
+    if matches.opt_present("profile") && matches.opt_present("private") {
+        return Err("--profile and --private can't be used together".to_owned());
+    }
+
+    let kiosk_commands: Vec<String> = matches.opt_str("kiosk-commands").map_or(vec![], |list| {
+        list.split(',').map(|name| name.trim().to_owned()).filter(|name| !name.is_empty()).collect()
+    });
+    if let Some(name) = kiosk_commands.iter().find(|name| !kiosk::COMMAND_NAMES.contains(&name.as_str())) {
+        return Err(format!("Unknown kiosk command: '{}'", name));
+    }
+
+    if matches.opt_present("record") && matches.opt_present("replay") {
+        return Err("--record and --replay can't be used together".to_owned());
+    }
+
+    if matches.opt_present("benchmark") && (matches.opt_present("replay") || matches.opt_present("kiosk")) {
+        return Err("--benchmark can't be used with --replay or --kiosk".to_owned());
+    }
+    for name in &["runs", "settle", "report"] {
+        if matches.opt_present(name) && !matches.opt_present("benchmark") {
+            return Err(format!("--{} requires --benchmark", name));
+        }
+    }
+
+    let mut servo_args = vec![];
+    for args in matches.opt_strs("servo-args") {
+        servo_args.extend(split_args(&args)?);
+    }
+
+    let kiosk_idle = parse_positive(&matches, "kiosk-idle")?;
+    let kiosk_rotate = parse_positive(&matches, "kiosk-rotate")?;
+    let benchmark_runs = parse_positive(&matches, "runs")?.unwrap_or(DEFAULT_BENCHMARK_RUNS);
+    // No settle time is fine
+    let benchmark_settle = parse_number(&matches, "settle")?.unwrap_or(DEFAULT_BENCHMARK_SETTLE);
+
+    Ok(ShellOptions {
+        help: matches.opt_present("help"),
+        version: matches.opt_present("version"),
+        resources_path: matches.opt_str("resources-path").map(PathBuf::from),
+        window_size: window_size,
+        log_level: log_level,
+        profile: matches.opt_str("profile"),
+        // Benchmark loads shouldn't share the cache and cookies of other runs
+        private: matches.opt_present("private") ||
+                 (matches.opt_present("benchmark") && !matches.opt_present("profile")),
+        user_agent: matches.opt_str("user-agent"),
+        new_window: matches.opt_present("new-window"),
+        webdriver_port: webdriver_port,
+        filter_lists: matches.opt_strs("filter-list").into_iter().map(PathBuf::from).collect(),
+        kiosk: matches.opt_present("kiosk"),
+        kiosk_allow: matches.opt_strs("kiosk-allow"),
+        kiosk_commands: kiosk_commands,
+        kiosk_idle: kiosk_idle,
+        kiosk_rotate: kiosk_rotate,
+        record: matches.opt_str("record").map(PathBuf::from),
+        replay: matches.opt_str("replay").map(PathBuf::from),
+        benchmark: matches.opt_str("benchmark").map(PathBuf::from),
+        benchmark_runs: benchmark_runs,
+        benchmark_settle: benchmark_settle,
+        benchmark_report: matches.opt_str("report").map(PathBuf::from),
+        servo_args: servo_args,
+        urls: matches.free,
+    })
+}
@@ -0,0 +1,279 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Top-level navigation filter. Rules use a subset of the hosts file and
+//! Adblock syntaxes, one rule per line:
+//!
+//! ```text
+//! # Comment (also "!")
+//! 0.0.0.0 example.com a.com    block these hosts (hosts file)
+//! ||example.com^               block this host and its subdomains
+//! ||example.com/admin          … only under this path
+//! |https://example.org/login   block URLs starting with this
+//! /checkout/*/pay              block URLs containing this
+//! @@||dev.example.com^         exception: never block this
+//! ```
+//!
+//! `*` matches anything, `^` matches a separator (anything but a letter,
+//! a digit, or one of `_-.%`) or the end of the URL. Rules with options
+//! (`$…`) only apply if the option is `document`. Element hiding rules
+//! are ignored.
+
+use servo::ServoUrl;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+enum Pattern {
+    // hosts file entry
+    ExactHost(String),
+    // ||host…, and the glob for the path and query
+    Host(String, Vec<u8>),
+    // |… and …, as a glob for the whole URL
+    Url(Vec<u8>),
+    // Not from a rule: matched as is, without wildcards
+    ExactUrl(String),
+    UrlPrefix(String),
+}
+
+pub struct Rule {
+    pattern: Pattern,
+    pub text: String,
+}
+
+// Indexes into Filter::rules. Host rules are looked up by host, the
+// other rules are tried one by one.
+struct RuleSet {
+    exact_hosts: HashMap<String, Vec<usize>>,
+    domains: HashMap<String, Vec<usize>>,
+    urls: Vec<usize>,
+}
+
+// The URL being checked, lowercased once for all the rules.
+struct Target {
+    url: String,
+    host: String,
+    // Path and query
+    path: String,
+}
+
+pub struct Filter {
+    rules: Vec<Rule>,
+    blocking: RuleSet,
+    exceptions: RuleSet,
+}
+
+impl Filter {
+    pub fn new() -> Filter {
+        Filter {
+            rules: vec![],
+            blocking: RuleSet::new(),
+            exceptions: RuleSet::new(),
+        }
+    }
+
+    pub fn load(&mut self, path: &Path) -> io::Result<()> {
+        let mut content = String::new();
+        File::open(path)?.read_to_string(&mut content)?;
+        let count = self.rules.len();
+        for line in content.lines() {
+            self.add_rule(line);
+        }
+        info!("Loaded {} filter rules from {:?}", self.rules.len() - count, path);
+        Ok(())
+    }
+
+    pub fn add_rule(&mut self, line: &str) {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('!') || line.starts_with('#') ||
+           line.starts_with('[') || line.contains("##") || line.contains("#@#") {
+            return;
+        }
+
+        let text = line.to_owned();
+        let (exception, line) = if line.starts_with("@@") {
+            (true, &line[2..])
+        } else {
+            (false, line)
+        };
+
+        let line = match line.find('$') {
+            Some(i) if &line[i + 1..] == "document" => &line[..i],
+            Some(_) => return,
+            None => line,
+        };
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let patterns = match words.split_first() {
+            // A hosts file entry can list several hosts, and end with a comment
+            Some((&ip, hosts)) if !hosts.is_empty() => {
+                if ip != "0.0.0.0" && ip != "127.0.0.1" && ip != "::1" {
+                    return;
+                }
+                hosts.iter()
+                     .take_while(|host| !host.starts_with('#'))
+                     .filter(|&&host| host != "localhost" && host != "0.0.0.0")
+                     .map(|host| Pattern::ExactHost(host.to_lowercase()))
+                     .collect()
+            }
+            Some((&pattern, _)) => {
+                let pattern = pattern.to_lowercase();
+                if pattern.starts_with("||") {
+                    let pattern = &pattern[2..];
+                    let end = pattern.find(|c| c == '/' || c == '^' || c == '*' || c == ':')
+                                     .unwrap_or(pattern.len());
+                    vec![Pattern::Host(pattern[..end].to_owned(), prefix_glob(&pattern[end..]))]
+                } else if pattern.starts_with('|') {
+                    vec![Pattern::Url(prefix_glob(&pattern[1..]))]
+                } else {
+                    vec![Pattern::Url(prefix_glob(&format!("*{}", pattern)))]
+                }
+            }
+            None => return,
+        };
+
+        for pattern in patterns {
+            self.push(pattern, text.clone(), exception);
+        }
+    }
+
+    /// Match this URL, taken literally.
+    pub fn add_exact_url(&mut self, url: &str) {
+        self.push(Pattern::ExactUrl(url.to_lowercase()), url.to_owned(), false);
+    }
+
+    /// Match the URLs starting with this prefix, taken literally.
+    pub fn add_url_prefix(&mut self, prefix: &str) {
+        self.push(Pattern::UrlPrefix(prefix.to_lowercase()), prefix.to_owned(), false);
+    }
+
+    fn push(&mut self, pattern: Pattern, text: String, exception: bool) {
+        let index = self.rules.len();
+        let set = if exception { &mut self.exceptions } else { &mut self.blocking };
+        match pattern {
+            Pattern::ExactHost(ref host) => {
+                set.exact_hosts.entry(host.clone()).or_insert_with(Vec::new).push(index)
+            }
+            Pattern::Host(ref host, _) => {
+                set.domains.entry(host.clone()).or_insert_with(Vec::new).push(index)
+            }
+            Pattern::Url(_) | Pattern::ExactUrl(_) | Pattern::UrlPrefix(_) => set.urls.push(index),
+        }
+        self.rules.push(Rule { pattern, text });
+    }
+
+    /// The rule blocking this URL, if any.
+    pub fn check(&self, url: &ServoUrl) -> Option<&Rule> {
+        let mut path = url.path().to_lowercase();
+        if let Some(query) = url.query() {
+            path.push('?');
+            path.push_str(&query.to_lowercase());
+        }
+        let target = Target {
+            url: url.as_str().to_lowercase(),
+            host: url.host_str().unwrap_or("").to_lowercase(),
+            path: path,
+        };
+        let blocking = self.blocking.find(&self.rules, &target);
+        if blocking.is_some() && self.exceptions.find(&self.rules, &target).is_some() {
+            return None;
+        }
+        blocking.map(|index| &self.rules[index])
+    }
+}
+
+impl RuleSet {
+    fn new() -> RuleSet {
+        RuleSet {
+            exact_hosts: HashMap::new(),
+            domains: HashMap::new(),
+            urls: vec![],
+        }
+    }
+
+    // The first matching rule, in the order of the rule files.
+    fn find(&self, rules: &[Rule], target: &Target) -> Option<usize> {
+        let mut candidates = vec![];
+        if let Some(indexes) = self.exact_hosts.get(&target.host) {
+            candidates.extend(indexes.iter().cloned());
+        }
+        // example.com matches ||example.com and ||com
+        let mut domain = target.host.as_str();
+        loop {
+            if let Some(indexes) = self.domains.get(domain) {
+                candidates.extend(indexes.iter().cloned().filter(|&i| rules[i].matches(target)));
+            }
+            match domain.find('.') {
+                Some(i) => domain = &domain[i + 1..],
+                None => break,
+            }
+        }
+        let first_url_rule = self.urls.iter().cloned().find(|&i| rules[i].matches(target));
+        candidates.into_iter().chain(first_url_rule).min()
+    }
+}
+
+impl Rule {
+    // Matching is case insensitive. Patterns are lowercase.
+    fn matches(&self, target: &Target) -> bool {
+        match self.pattern {
+            Pattern::ExactHost(ref host) => target.host == *host,
+            Pattern::Host(ref host, ref path) => {
+                let host_matches = target.host == *host || {
+                    target.host.ends_with(host.as_str()) &&
+                    target.host.as_bytes()[target.host.len() - host.len() - 1] == b'.'
+                };
+                host_matches && glob(path, target.path.as_bytes())
+            }
+            Pattern::Url(ref glob_pattern) => glob(glob_pattern, target.url.as_bytes()),
+            Pattern::ExactUrl(ref url) => target.url == *url,
+            Pattern::UrlPrefix(ref prefix) => target.url.starts_with(prefix.as_str()),
+        }
+    }
+}
+
+// The pattern matches the beginning of the text. A trailing `|` anchors
+// the pattern to the end of the text.
+fn prefix_glob(pattern: &str) -> Vec<u8> {
+    if pattern.ends_with('|') {
+        pattern[..pattern.len() - 1].as_bytes().to_vec()
+    } else {
+        format!("{}*", pattern).into_bytes()
+    }
+}
+
+fn is_separator(c: u8) -> bool {
+    match c {
+        b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' | b'_' | b'-' | b'.' | b'%' => false,
+        _ => true,
+    }
+}
+
+// Iterative, with backtracking to the last `*` only: linear in the
+// text length for most patterns, never exponential.
+fn glob(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // Pattern position after the last `*`, and where it started in the text
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            p += 1;
+            star = Some((p, t));
+        } else if p < pattern.len() &&
+                  (pattern[p] == text[t] || (pattern[p] == b'^' && is_separator(text[t]))) {
+            p += 1;
+            t += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // The `*` takes one more character
+            p = star_p;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    // `^` also matches the end of the text
+    pattern[p..].iter().all(|&c| c == b'*' || c == b'^')
+}
@@ -0,0 +1,137 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Kiosk mode: fullscreen without chrome, most commands disabled,
+//! navigation restricted to an allowlist. The first URL is the home page.
+//! The browser goes back home when idle, or rotates through the URLs.
+
+use filter::Filter;
+use servo::ServoUrl;
+use state::WindowState;
+use std::time::{Duration, Instant};
+use traits::window::WindowCommand;
+
+/// Names used by --kiosk-commands.
+pub const COMMAND_NAMES: &'static [&'static str] = &[
+    "reload", "stop", "back", "forward", "location", "zoom", "tabs", "sidebar", "options",
+    "open-in-default-browser", "copy-url",
+];
+
+/// History jumps are "back" or "forward" depending on the current entry
+/// of the current tab.
+pub fn command_name(cmd: &WindowCommand, state: &WindowState) -> &'static str {
+    match *cmd {
+        WindowCommand::Reload => "reload",
+        WindowCommand::Stop => "stop",
+        WindowCommand::NavigateBack => "back",
+        WindowCommand::NavigateForward => "forward",
+        WindowCommand::NavigateTo(index) => {
+            let current = state.current_browser_index.map_or(0, |idx| state.browsers[idx].history_index);
+            if index < current { "back" } else { "forward" }
+        }
+        WindowCommand::OpenLocation |
+        WindowCommand::PasteAndGo |
+        WindowCommand::Load(_) => "location",
+        WindowCommand::CopyUrl |
+        WindowCommand::CopyLink(_) => "copy-url",
+        WindowCommand::ZoomIn |
+        WindowCommand::ZoomOut |
+        WindowCommand::ZoomToActualSize => "zoom",
+        WindowCommand::NewTab |
+        WindowCommand::OpenInNewTab(_) |
+        WindowCommand::CloseTab |
+        WindowCommand::ReopenClosedTab |
+        WindowCommand::NextTab |
+        WindowCommand::PrevTab |
+        WindowCommand::SelectTab(_) => "tabs",
+        WindowCommand::ToggleSidebar => "sidebar",
+        WindowCommand::OpenInDefaultBrowser => "open-in-default-browser",
+        WindowCommand::ShowOptions |
+        WindowCommand::ToggleOptionShowLogs |
+        WindowCommand::ToggleOptionSmoothScrolling |
+        WindowCommand::ToggleOptionKineticScrolling |
+        WindowCommand::ToggleOptionFragmentBorders |
+        WindowCommand::ToggleOptionParallelDisplayListBuidling |
+        WindowCommand::ToggleOptionShowParallelLayout |
+        WindowCommand::ToggleOptionConvertMouseToTouch |
+        WindowCommand::ToggleOptionTileBorders |
+        WindowCommand::ToggleOptionWRProfiler |
+        WindowCommand::ToggleOptionWRTextureCacheDebug |
+        WindowCommand::ToggleOptionWRTargetDebug => "options",
+    }
+}
+
+pub struct Kiosk {
+    urls: Vec<String>,
+    allowed_commands: Vec<String>,
+    pub idle_timeout: Option<Duration>,
+    pub rotate_interval: Option<Duration>,
+    last_activity: Instant,
+    current_url: usize,
+}
+
+impl Kiosk {
+    pub fn new(urls: Vec<String>,
+               allowed_commands: Vec<String>,
+               idle_timeout: Option<Duration>,
+               rotate_interval: Option<Duration>) -> Kiosk {
+        Kiosk {
+            urls,
+            allowed_commands,
+            idle_timeout,
+            rotate_interval,
+            last_activity: Instant::now(),
+            current_url: 0,
+        }
+    }
+
+    pub fn home_url(&self) -> &str {
+        &self.urls[0]
+    }
+
+    pub fn allows(&self, cmd: &WindowCommand, state: &WindowState) -> bool {
+        let name = command_name(cmd, state);
+        self.allowed_commands.iter().any(|allowed| allowed == name)
+    }
+
+    /// Only the kiosk URLs, their hosts, the given hosts and the pages
+    /// generated by the shell are allowed. A URL matching one of the rules
+    /// is allowed, unless the navigation filter blocks it.
+    pub fn allowlist(&self, allowed_hosts: &[String], pages_dir: &str) -> Filter {
+        let mut allowlist = Filter::new();
+        allowlist.add_url_prefix(pages_dir);
+        for url in &self.urls {
+            allowlist.add_exact_url(url);
+            if let Some(host) = ServoUrl::parse(url).ok().as_ref().and_then(|url| url.host_str()) {
+                allowlist.add_rule(&format!("||{}^", host));
+            }
+        }
+        for host in allowed_hosts {
+            allowlist.add_rule(&format!("||{}^", host));
+        }
+        allowlist
+    }
+
+    /// User input happened.
+    pub fn record_activity(&mut self) {
+        self.last_activity = Instant::now();
+    }
+
+    pub fn idle_time(&self) -> Duration {
+        self.last_activity.elapsed()
+    }
+
+    /// The next URL of the rotation.
+    pub fn next_url(&mut self) -> &str {
+        self.current_url = (self.current_url + 1) % self.urls.len();
+        &self.urls[self.current_url]
+    }
+
+    /// Going home restarts the rotation.
+    pub fn go_home(&mut self) -> &str {
+        self.current_url = 0;
+        self.record_activity();
+        self.home_url()
+    }
+}
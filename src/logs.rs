@@ -3,42 +3,128 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use log::*;
+use std::collections::VecDeque;
+use std::io::{self, Write};
 use std::sync::{Arc, Mutex};
+#[cfg(unix)]
+use std::fs::File;
+#[cfg(unix)]
+use std::sync::mpsc::Receiver;
 
+// How many logs are kept around for servoshell://logs
+const MAX_BUFFERED_LOGS: usize = 2000;
+
+// Servo's console API (console.log & co) writes to stdout. Captured
+// lines are recorded under this target.
+pub const CONSOLE_TARGET: &'static str = "console";
+
+// Targets shown in the "console" view of servoshell://logs. Uncaught
+// JS exceptions are reported by Servo through the bindings error module.
+pub const CONSOLE_TARGETS: &'static [&'static str] = &[
+    CONSOLE_TARGET,
+    "script::dom::bindings::error",
+];
+
+#[derive(Clone)]
 pub struct ShellLog {
     pub level: LogLevel,
     pub target: String,
     pub message: String,
 }
 
-pub struct ShellLogs(Mutex<Vec<ShellLog>>);
+impl ShellLog {
+    pub fn is_console(&self) -> bool {
+        CONSOLE_TARGETS.iter().any(|t| self.target.starts_with(t))
+    }
+}
+
+pub struct ShellLogs {
+    pending: Mutex<Vec<ShellLog>>,
+    buffer: Mutex<VecDeque<ShellLog>>,
+    stdout_capture: Mutex<Option<StdoutCapture>>,
+}
+
+#[cfg(unix)]
+struct StdoutCapture {
+    // The stdout from before the capture
+    original: File,
+    // Gets a message once the forwarder reached the end of the pipe
+    done: Receiver<()>,
+}
+
+#[cfg(not(unix))]
+#[allow(dead_code)]
+struct StdoutCapture;
 
 impl ShellLogs {
+    fn new() -> ShellLogs {
+        ShellLogs {
+            pending: Mutex::new(Vec::new()),
+            buffer: Mutex::new(VecDeque::with_capacity(MAX_BUFFERED_LOGS)),
+            stdout_capture: Mutex::new(None),
+        }
+    }
+
+    fn push(&self, log: ShellLog) {
+        {
+            let mut buffer = self.buffer.lock().unwrap();
+            if buffer.len() == MAX_BUFFERED_LOGS {
+                buffer.pop_front();
+            }
+            buffer.push_back(log.clone());
+        }
+        self.pending.lock().unwrap().push(log);
+    }
+
+    /// Logs recorded since the last call.
     pub fn get_logs(&self) -> Vec<ShellLog> {
-        let mut vec = self.0.lock().unwrap();
+        let mut vec = self.pending.lock().unwrap();
         let res = vec.drain(..).collect();
         res
     }
+
+    /// The last MAX_BUFFERED_LOGS logs, oldest first.
+    pub fn get_buffered_logs(&self) -> Vec<ShellLog> {
+        self.buffer.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// The real stdout, for output that isn't a console log.
+    pub fn stdout(&self) -> Box<Write> {
+        match *self.stdout_capture.lock().unwrap() {
+            Some(ref capture) => original_stdout(capture),
+            None => Box::new(io::stdout()),
+        }
+    }
+
+    /// Give stdout back, once what's left in the pipe is forwarded.
+    pub fn stop_capture(&self) {
+        if let Some(capture) = self.stdout_capture.lock().unwrap().take() {
+            restore_stdout(capture);
+        }
+    }
 }
 
-pub struct Logger(Arc<ShellLogs>);
+pub struct Logger(Arc<ShellLogs>, LogLevelFilter);
 
 impl Logger {
-    pub fn init() -> Arc<ShellLogs> {
+    pub fn init(level: LogLevelFilter) -> Arc<ShellLogs> {
         let mut rv = None;
         set_logger(|max_log_level| {
-            max_log_level.set(LogLevelFilter::Info);
-            let logs = Arc::new(ShellLogs(Mutex::new(Vec::new())));
+            max_log_level.set(level);
+            let logs = Arc::new(ShellLogs::new());
             rv = Some(logs.clone());
-            Box::new(Logger(logs))
+            Box::new(Logger(logs, level))
         }).unwrap();
-        rv.unwrap()
+        let logs = rv.unwrap();
+        #[cfg(unix)]
+        capture_stdout(logs.clone());
+        logs
     }
 }
 
 impl Log for Logger {
     fn enabled(&self, metadata: &LogMetadata) -> bool {
-        metadata.level() <= LogLevel::Info
+        metadata.level() <= self.1
     }
 
     fn log(&self, record: &LogRecord) {
@@ -48,7 +134,97 @@ impl Log for Logger {
                 message: format!("{}", record.args()),
                 target: format!("{}", record.target()),
             };
-            (self.0).0.lock().unwrap().push(log);
+            self.0.push(log);
+        }
+    }
+}
+
+/// Redirect stdout to a pipe. Each line is forwarded to the original
+/// stdout and recorded as a console log.
+#[cfg(unix)]
+fn capture_stdout(logs: Arc<ShellLogs>) {
+    use libc;
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::io::FromRawFd;
+    use std::sync::mpsc::channel;
+    use std::thread;
+
+    let mut fds = [0; 2];
+    let (reader, mut original) = unsafe {
+        if libc::pipe(fds.as_mut_ptr()) != 0 {
+            warn!("Can't capture console output: pipe() failed");
+            return;
+        }
+        let original = libc::dup(libc::STDOUT_FILENO);
+        if original < 0 || libc::dup2(fds[1], libc::STDOUT_FILENO) < 0 {
+            warn!("Can't capture console output: dup() failed");
+            libc::close(fds[0]);
+            libc::close(fds[1]);
+            return;
+        }
+        libc::close(fds[1]);
+        (File::from_raw_fd(fds[0]), File::from_raw_fd(original))
+    };
+
+    let (done_sender, done) = channel();
+    match original.try_clone() {
+        Ok(original) => *logs.stdout_capture.lock().unwrap() = Some(StdoutCapture { original, done }),
+        Err(err) => warn!("Stdout can't be restored: {}", err),
+    }
+
+    thread::spawn(move || {
+        // Pages can print anything, not only UTF-8
+        let mut reader = BufReader::new(reader);
+        let mut bytes = vec![];
+        loop {
+            bytes.clear();
+            match reader.read_until(b'\n', &mut bytes) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(_) => break,
+            }
+            original.write_all(&bytes).ok();
+            let line = String::from_utf8_lossy(&bytes).trim_right_matches(&['\n', '\r'][..]).to_owned();
+            logs.push(ShellLog {
+                level: LogLevel::Info,
+                target: CONSOLE_TARGET.to_owned(),
+                message: line,
+            });
         }
+        done_sender.send(()).ok();
+    });
+}
+
+#[cfg(unix)]
+fn original_stdout(capture: &StdoutCapture) -> Box<Write> {
+    match capture.original.try_clone() {
+        Ok(original) => Box::new(original),
+        Err(_) => Box::new(io::stdout()),
+    }
+}
+
+#[cfg(unix)]
+fn restore_stdout(capture: StdoutCapture) {
+    use libc;
+    use std::os::unix::io::AsRawFd;
+    use std::time::Duration;
+    io::stdout().flush().ok();
+    // Closes the pipe, so the forwarder reaches its end
+    if unsafe { libc::dup2(capture.original.as_raw_fd(), libc::STDOUT_FILENO) } < 0 {
+        warn!("Can't restore stdout");
+        return;
     }
+    // Content processes can keep the pipe open
+    if capture.done.recv_timeout(Duration::from_secs(1)).is_err() {
+        warn!("Console output still captured at exit");
+    }
+}
+
+#[cfg(not(unix))]
+fn original_stdout(_capture: &StdoutCapture) -> Box<Write> {
+    Box::new(io::stdout())
+}
+
+#[cfg(not(unix))]
+fn restore_stdout(_capture: StdoutCapture) {
 }
@@ -8,7 +8,7 @@
 #[macro_use]
 extern crate log;
 
-#[cfg(all(not(feature = "force-glutin"), target_os = "macos"))]
+#[cfg(unix)]
 extern crate libc;
 #[cfg(all(not(feature = "force-glutin"), target_os = "macos"))]
 extern crate cocoa;
@@ -20,6 +20,8 @@ extern crate objc;
 extern crate glutin;
 #[cfg(all(not(feature = "force-gtk"), any(feature = "force-glutin", not(target_os = "macos"))))]
 extern crate tinyfiledialogs;
+#[cfg(all(not(feature = "force-gtk"), any(feature = "force-glutin", not(target_os = "macos"))))]
+extern crate clipboard;
 
 #[cfg(feature = "force-gtk")]
 extern crate epoxy;
@@ -41,83 +43,340 @@ extern crate user32;
 #[cfg(target_os = "windows")]
 extern crate gdi32;
 
+extern crate getopts;
 extern crate open;
+#[macro_use]
+extern crate serde_derive;
+#[macro_use]
+extern crate serde_json;
 
+mod benchmark;
+mod cli;
+mod filter;
+mod kiosk;
 mod traits;
 mod platform;
 mod servo;
 mod state;
 mod logs;
+mod pages;
+mod perf;
+mod profile;
+mod record;
+mod remote;
+mod scroll;
+mod shortcuts;
+mod timers;
 
+use benchmark::Benchmark;
+use filter::Filter;
+use kiosk::Kiosk;
 use platform::App;
-use servo::{Servo, ServoEvent, ServoUrl, WebRenderDebugOption};
-use std::env::args;
-use state::{AppState, WindowState};
+use pages::PageContext;
+use perf::Perf;
+use profile::Profile;
+use record::{Input, Recorder, Replayer, Session};
+use remote::RemoteCall;
+use scroll::Scroller;
+use servo::{BrowserId, Servo, ServoEvent, ServoUrl, WebRenderDebugOption};
+use std::cell::Cell;
+use std::cmp;
+use std::u64;
+use std::collections::HashMap;
+use std::env::{self, args};
+use std::path::Path;
+use std::process;
+use std::time::{Duration, Instant};
+use state::{AppState, BrowserState, ClosedTab, ContextMenuItem, Focus, LoadError, LoadErrorKind, WindowState};
+use timers::{TimerEvent, TimerId, Timers};
 use traits::app::{AppEvent, AppCommand, AppMethods};
 use traits::view::*;
 use traits::window::{WindowEvent, WindowCommand};
 
 const PKG_VERSION: &'static str = env!("CARGO_PKG_VERSION");
 const PKG_NAME: &'static str = env!("CARGO_PKG_NAME");
+// A load fails if the server doesn't respond in time
+const LOAD_TIMEOUT_SECS: u64 = 30;
+// A benchmark load that doesn't end by then counts as failed. Normally
+// the load timeout shows the error page earlier.
+const BENCHMARK_TIMEOUT_SECS: u64 = 2 * LOAD_TIMEOUT_SECS;
+// How long a shortcut key waits for the page to not consume it. Servo
+// replies asynchronously, later on a busy page.
+const SHORTCUT_TIMEOUT_MS: u64 = 3000;
+// How long the zoom level stays in the status area
+const ZOOM_INDICATOR_MS: u64 = 1500;
+// Visual zoom range. Servo doesn't report the pinch zoom level, so it's
+// clamped here, within Servo's own limits.
+const MIN_PINCH_ZOOM: f32 = 1.0;
+const MAX_PINCH_ZOOM: f32 = 8.0;
+// Finger id of the touches emulated with the mouse
+const MOUSE_TOUCH_ID: u64 = u64::MAX;
+// URLs given on the command line without "://" need one of these schemes,
+// otherwise they're hosts
+const KNOWN_SCHEMES: &'static [&'static str] = &["about", "data", "file", "http", "https", "javascript", "mailto"];
 
 fn main() {
+    // The destructors run before run() returns, not after process::exit()
+    let code = run();
+    if code != 0 {
+        process::exit(code);
+    }
+}
+
+// The exit code
+fn run() -> i32 {
+    let args: Vec<String> = args().collect();
+
+    if let Some(token) = cli::content_process_token(&args[1..]) {
+        Servo::run_content_process(token);
+        return 0;
+    }
+
+    let options = match cli::parse(&args[1..]) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}: {}", PKG_NAME, error);
+            eprintln!("Try '{} --help' for more information.", PKG_NAME);
+            return 1;
+        }
+    };
+
+    if options.help {
+        println!("{}", cli::usage(PKG_NAME));
+        return 0;
+    }
+
+    if options.version {
+        println!("{} {}", PKG_NAME, PKG_VERSION);
+        return 0;
+    }
+
+    let profile = if options.private {
+        Profile::private()
+    } else {
+        Profile::new(options.profile.as_ref().map_or(profile::DEFAULT_PROFILE, |p| p.as_str()))
+    };
+    let profile = match profile {
+        Ok(profile) => profile,
+        Err(err) => {
+            eprintln!("{}: Can't create profile: {}", PKG_NAME, err);
+            return 1;
+        }
+    };
+
+    let urls: Vec<String> = options.urls.iter().map(|arg| url_from_arg(arg)).collect();
+
+    // If a browser is already running with this profile, it opens the URLs
+    if let Some(socket_path) = profile.socket_path() {
+        if !options.new_window && options.benchmark.is_none() && remote::forward_urls(&socket_path, &urls) {
+            return 0;
+        }
+    }
+
     #[cfg(feature = "force-gtk")]
     gtk::init().unwrap();
 
-    let logs = logs::Logger::init();
+    let logs = logs::Logger::init(options.log_level);
 
     info!("starting");
 
-    args().find(|arg| arg == "--version").map(|_| {
-        println!("{} {}", PKG_NAME, PKG_VERSION);
-        std::process::exit(0);
-    });
+    let resources_path = options.resources_path.clone()
+        .or_else(|| App::get_resources_path())
+        .expect("Can't find resources path");
+
+    info!("Profile: {:?}", profile.dir());
+
+    Servo::configure(resources_path.clone(), options.servo_args(&profile));
+
+    let home_url = resources_path.parent().unwrap().join("shell_resources").join("home.html");
+    let home_url = ServoUrl::from_file_path(&home_url).unwrap().into_string();
+
+    let urls = if urls.is_empty() {
+        vec![home_url]
+    } else {
+        urls
+    };
+
+    let mut filter = Filter::new();
+    for path in &options.filter_lists {
+        if let Err(err) = filter.load(path) {
+            eprintln!("{}: Can't read filter list {:?}: {}", PKG_NAME, path, err);
+            return 1;
+        }
+    }
+
+    let mut kiosk = if options.kiosk {
+        let kiosk = Kiosk::new(urls.clone(),
+                               options.kiosk_commands.clone(),
+                               options.kiosk_idle.map(|minutes| Duration::from_secs(minutes * 60)),
+                               options.kiosk_rotate.map(Duration::from_secs));
+        Some(kiosk)
+    } else {
+        None
+    };
 
-    let resources_path = App::get_resources_path().expect("Can't find resources path");
+    let replay = match options.replay {
+        Some(ref path) => match Session::load(path) {
+            Ok(session) => Some(session),
+            Err(err) => {
+                eprintln!("{}: Can't read session {:?}: {}", PKG_NAME, path, err);
+                return 1;
+            }
+        },
+        None => None,
+    };
+
+    let mut benchmark = match options.benchmark {
+        Some(ref path) => {
+            let urls = match benchmark::read_urls(path) {
+                Ok(urls) => urls,
+                Err(err) => {
+                    eprintln!("{}: Can't read benchmark URLs {:?}: {}", PKG_NAME, path, err);
+                    return 1;
+                }
+            };
+            if urls.is_empty() {
+                eprintln!("{}: No URLs in {:?}", PKG_NAME, path);
+                return 1;
+            }
+            let urls: Vec<String> = urls.iter().map(|arg| url_from_arg(arg)).collect();
+            if let Some(url) = urls.iter().find(|url| ServoUrl::parse(url).is_err()) {
+                eprintln!("{}: Invalid benchmark URL: '{}'", PKG_NAME, url);
+                return 1;
+            }
+            Some(Benchmark::new(urls, options.benchmark_runs, Duration::from_millis(options.benchmark_settle)))
+        }
+        None => None,
+    };
+
+    // A replayed session starts like the recorded one
+    let window_size = replay.as_ref().map_or(options.window_size, |session| session.window_size);
 
     #[cfg(not(feature = "force-gtk"))]
     let app = App::new().expect("Can't create application");
     #[cfg(feature = "force-gtk")]
     let mut app = App::new().expect("Can't create application");
-    let win = app.new_window().expect("Can't create application");
+    let win = app.new_window(window_size, options.kiosk).expect("Can't create application");
 
     let view = win.new_view().unwrap();
 
-    Servo::configure(resources_path.clone());
-
     let servo = {
         let geometry = view.get_geometry();
         let waker = win.new_event_loop_waker();
-        Servo::new(geometry, view.clone(), waker)
+        Servo::new(geometry, view.clone(), waker, win.supports_clipboard())
     };
+    servo.set_filter(filter);
+    if let Some(ref kiosk) = kiosk {
+        servo.set_allowlist(kiosk.allowlist(&options.kiosk_allow, &pages::pages_dir_url(&profile)));
+    }
+
     #[cfg(feature = "force-gtk")]
     let mut rx = app.take_receiver().unwrap();
     #[cfg(feature = "force-gtk")]
     servo.connect(&mut rx);
 
-    let home_url = resources_path.parent().unwrap().join("shell_resources").join("home.html");
-    let home_url = ServoUrl::from_file_path(&home_url).unwrap().into_string();
-
-    // Skip first argument (executable), and find the first
-    // argument that doesn't start with `-`
-    let url = args().skip(1).find(|arg| {
-        !arg.starts_with("-")
-    }).unwrap_or(home_url);
-
-    let browser = servo.new_browser(&url);
-    servo.select_browser(browser.id);
+    // In kiosk mode, the other URLs are shown one after the other
+    let urls = match kiosk {
+        Some(ref kiosk) => vec![kiosk.home_url().to_owned()],
+        None => urls,
+    };
+    let urls = match replay {
+        Some(ref session) => session.urls.clone(),
+        None => urls,
+    };
+    // The benchmark loads its URLs one after the other, in the first tab
+    let urls = match benchmark {
+        Some(ref mut benchmark) => vec![benchmark.start_next().unwrap()],
+        None => urls,
+    };
 
     let mut app_state = AppState::new();
     app_state.current_window_index = Some(0);
 
     let mut win_state = WindowState::new();
+    win_state.private = profile.is_private();
+    for url in &urls {
+        let browser = servo.new_browser(url);
+        win_state.browsers.push(browser);
+    }
     win_state.current_browser_index = Some(0);
-    win_state.browsers.push(browser);
+    win_state.focus = Focus::Browser(win_state.browsers[0].id);
+    servo.select_browser(win_state.browsers[0].id);
+
+    // A benchmark doesn't take the URLs of other instances
+    let remote = profile.socket_path().and_then(|path| {
+        if options.benchmark.is_some() {
+            return None;
+        }
+        remote::listen(&path, win.new_event_loop_waker())
+    });
 
     app.render(&app_state);
     win.render(&win_state);
 
-    info!("Servo version: {}", servo.version());
+    let servo_version = servo.version();
+    info!("Servo version: {}", servo_version);
+
+    // Commands issued by the shell itself, handled on the next iteration
+    let mut pending_commands: Vec<WindowCommand> = vec![];
+
+    let perf = Perf::new();
+
+    let page_context = PageContext {
+        logs: &logs,
+        perf: &perf,
+        profile: &profile,
+        resources_path: &resources_path,
+        servo_version: &servo_version,
+    };
+
+    let timers = Timers::new(win.new_event_loop_waker());
+    let mut load_timers = HashMap::new();
+
+    let benchmark_timeout = Duration::from_secs(BENCHMARK_TIMEOUT_SECS);
+    let mut benchmark_timer = benchmark.as_ref().map(|_| {
+        timers.schedule(benchmark_timeout, TimerEvent::BenchmarkNext)
+    });
+    // Set once the benchmark is done. app.run() then returns.
+    let exit_code = Cell::new(0);
+
+    if let Some(ref kiosk) = kiosk {
+        if let Some(timeout) = kiosk.idle_timeout {
+            timers.schedule(timeout, TimerEvent::KioskIdle);
+        }
+        if let Some(interval) = kiosk.rotate_interval {
+            timers.schedule(interval, TimerEvent::KioskRotate);
+        }
+    }
+
+    // Ctrl (Cmd on Mac) is held down. Clicks then open links in new tabs.
+    let mut cmd_or_ctrl_down = false;
+    // Ctrl is held down. The wheel then zooms.
+    let mut ctrl_down = false;
+    let mut zoom_indicator_timer: Option<TimerId> = None;
+
+    // Last known mouse position. Scrolling happens there.
+    let mut pointer = (0, 0);
+    let mut scroller = Scroller::new();
+    let mut scroll_frame_scheduled = false;
+    // Shortcut keys sent to the page, waiting to come back unconsumed.
+    // Servo sends nothing back for the keys the page consumed: they expire.
+    let mut pending_shortcuts: Vec<(Key, KeyModifiers, Instant)> = vec![];
+    // Left button held down while mouse events are converted to touches
+    let mut mouse_touch_down = false;
+
+    let mut recorder = options.record.as_ref().map(|path| {
+        Recorder::new(path.clone(), urls.clone(), window_size, view.get_geometry())
+    });
+    let mut replayer = replay.map(|session| {
+        let geometry = view.get_geometry();
+        if geometry.view_size != session.view_size || geometry.hidpi_factor != session.hidpi_factor {
+            warn!("Replaying in a {:?} view at {}x, recorded in a {:?} view at {}x",
+                  geometry.view_size, geometry.hidpi_factor, session.view_size, session.hidpi_factor);
+        }
+        Replayer::new(session)
+    });
+    let mut replay_timer_scheduled = false;
 
     let handle_events = || {
 
@@ -128,17 +387,93 @@ fn main() {
             let before_win_state = win_state.clone();
 
             let app_events = app.get_events();
-            let win_events = win.get_events();
-            let view_events = view.get_events();
+            let mut win_events = win.get_events();
+            if let Some(ref kiosk) = kiosk {
+                win_events.retain(|event| {
+                    match *event {
+                        WindowEvent::DoCommand(ref cmd) if !kiosk.allows(cmd, &win_state) => {
+                            info!("Command disabled in kiosk mode: {:?}", cmd);
+                            false
+                        }
+                        _ => true,
+                    }
+                });
+            }
+            let mut view_events = view.get_events();
+            // Commands issued by the shell are not recorded. The replayed
+            // events issue them again.
+            if let Some(ref mut recorder) = recorder {
+                for event in &win_events {
+                    if let WindowEvent::DoCommand(ref cmd) = *event {
+                        recorder.record(Input::Command(cmd.clone()));
+                    }
+                }
+                for event in &view_events {
+                    recorder.record(Input::View(event.clone()));
+                }
+            }
+            win_events.extend(pending_commands.drain(..).map(WindowEvent::DoCommand));
+            if let Some(ref mut replayer) = replayer {
+                // Only the replayed input counts
+                view_events.retain(|event| {
+                    match *event {
+                        ViewEvent::GeometryDidChange => true,
+                        _ => false,
+                    }
+                });
+                for input in replayer.get_events() {
+                    match input {
+                        Input::View(event) => view_events.push(event),
+                        Input::Command(cmd) => win_events.push(WindowEvent::DoCommand(cmd)),
+                    }
+                }
+            }
             let servo_events = servo.get_events();
+            perf.frames_presented(&servo.take_frames());
+            let remote_requests = remote.as_ref().map_or(vec![], |r| r.get_requests());
+            let timer_events = timers.get_events();
 
             if app_events.is_empty() &&
                win_events.is_empty() &&
                view_events.is_empty() &&
-               servo_events.is_empty() {
+               servo_events.is_empty() &&
+               remote_requests.is_empty() &&
+               timer_events.is_empty() {
                    break
             }
 
+            // Show the zoom level if it changes
+            let mut zoom_changed = false;
+
+            // Remote commands are handled like commands from the UI
+            for request in remote_requests {
+                match request.call.clone() {
+                    RemoteCall::GetState => {
+                        request.reply(remote::state_to_json(&win_state));
+                    }
+                    RemoteCall::Commands(cmds) => {
+                        let count = win_state.browsers.len();
+                        let invalid_tab = cmds.iter().any(|cmd| {
+                            match *cmd {
+                                WindowCommand::SelectTab(idx) => idx >= count,
+                                _ => false,
+                            }
+                        });
+                        let disabled = kiosk.as_ref().map_or(false, |kiosk| {
+                            cmds.iter().any(|cmd| !kiosk.allows(cmd, &win_state))
+                        });
+                        if invalid_tab {
+                            request.reply_error("No such tab");
+                        } else if disabled {
+                            request.reply_error("Command disabled in kiosk mode");
+                        } else {
+                            win_events.extend(cmds.into_iter().map(WindowEvent::DoCommand));
+                            request.reply(serde_json::Value::Null);
+                        }
+                    }
+                }
+            }
+
             // FIXME: it's really annoying we need this
             let mut force_sync = false;
 
@@ -149,6 +484,13 @@ fn main() {
                     }
                     AppEvent::WillTerminate => {
                         // FIXME: does this work?
+                        // Cocoa exits without returning from app.run()
+                        profile.delete_if_private();
+                        if let Some(ref recorder) = recorder {
+                            if let Err(err) = recorder.save() {
+                                warn!("Can't save the session: {}", err);
+                            }
+                        }
                     }
                     AppEvent::DidChangeScreenParameters => {
                         // FIXME: does this work?
@@ -190,9 +532,24 @@ fn main() {
                         win_state.options_open = false;
                     }
                     WindowEvent::UrlbarFocusChanged(focused) => {
-                        win_state.urlbar_focused = focused;
+                        win_state.focus = if focused {
+                            Focus::Urlbar
+                        } else {
+                            Focus::Browser(win_state.browsers[win_state.current_browser_index.unwrap()].id)
+                        };
+                    }
+                    WindowEvent::WindowFocusChanged(focused) => {
+                        if !focused {
+                            servo.blur();
+                            cmd_or_ctrl_down = false;
+                            ctrl_down = false;
+                        }
+                    }
+                    WindowEvent::ContextMenuClosed => {
+                        win_state.context_menu = None;
                     }
                     WindowEvent::DoCommand(cmd) => {
+                        win_state.context_menu = None;
                         let idx = win_state.current_browser_index.unwrap();
                         let bid = win_state.browsers[idx].id;
                         match cmd {
@@ -200,33 +557,86 @@ fn main() {
                                 // FIXME
                             }
                             WindowCommand::Reload => {
-                                servo.reload(bid);
+                                // On an error page, retry the failed load
+                                let failed_url = win_state.browsers[idx].load_error.as_ref().and_then(|error| {
+                                    ServoUrl::parse(&error.url).ok()
+                                });
+                                match failed_url {
+                                    Some(url) => servo.load_url(bid, url),
+                                    None => servo.reload(bid),
+                                }
                             }
                             WindowCommand::NavigateBack => {
-                                servo.go_back(bid);
+                                servo.go_back(bid, 1);
                             }
                             WindowCommand::NavigateForward => {
-                                servo.go_forward(bid);
+                                servo.go_forward(bid, 1);
+                            }
+                            WindowCommand::NavigateTo(index) => {
+                                let current = win_state.browsers[idx].history_index;
+                                if index < current {
+                                    servo.go_back(bid, current - index);
+                                } else if index > current && index < win_state.browsers[idx].history.len() {
+                                    servo.go_forward(bid, index - current);
+                                }
                             }
                             WindowCommand::OpenLocation => {
-                                win_state.urlbar_focused = true;
+                                win_state.focus = Focus::Urlbar;
                             }
                             WindowCommand::OpenInDefaultBrowser => {
-                                if let Some(ref url) = win_state.browsers[idx].url {
+                                if let Some(url) = win_state.browsers[idx].display_url() {
                                     open::that(url.clone()).ok();
                                 }
                             }
+                            WindowCommand::CopyUrl => {
+                                if let Some(url) = win_state.browsers[idx].display_url() {
+                                    win.set_clipboard_contents(url);
+                                }
+                            }
+                            WindowCommand::CopyLink(url) => {
+                                win.set_clipboard_contents(&url);
+                            }
+                            WindowCommand::OpenInNewTab(url) => {
+                                match ServoUrl::parse(&url) {
+                                    Ok(url) => {
+                                        // Loaded after creation to go through allow_navigation
+                                        let browser = servo.new_browser("about:blank");
+                                        servo.select_browser(browser.id);
+                                        servo.update_geometry(view.get_geometry());
+                                        servo.load_url(browser.id, url);
+                                        win_state.current_browser_index = Some(win_state.browsers.len());
+                                        win_state.browsers.push(browser);
+                                    }
+                                    Err(err) => warn!("Can't parse url: {}", err),
+                                }
+                            }
+                            WindowCommand::PasteAndGo => {
+                                match win.get_clipboard_contents() {
+                                    Some(ref text) if !text.trim().is_empty() => {
+                                        pending_commands.push(WindowCommand::Load(text.trim().to_owned()));
+                                    }
+                                    _ => info!("Nothing to paste"),
+                                }
+                            }
                             WindowCommand::ZoomIn => {
                                 win_state.browsers[idx].zoom *= 1.1;
                                 servo.zoom(win_state.browsers[idx].zoom);
+                                zoom_changed = true;
                             }
                             WindowCommand::ZoomOut => {
                                 win_state.browsers[idx].zoom /= 1.1;
                                 servo.zoom(win_state.browsers[idx].zoom);
+                                zoom_changed = true;
                             }
                             WindowCommand::ZoomToActualSize => {
-                                win_state.browsers[idx].zoom = 1.0;
+                                let browser = &mut win_state.browsers[idx];
+                                browser.zoom = 1.0;
                                 servo.reset_zoom();
+                                if browser.pinch_zoom != 1.0 {
+                                    servo.pinch_zoom(1.0 / browser.pinch_zoom);
+                                    browser.pinch_zoom = 1.0;
+                                }
+                                zoom_changed = true;
                             }
 
                             WindowCommand::ToggleSidebar => {
@@ -239,7 +649,7 @@ fn main() {
 
                             WindowCommand::Load(request) => {
                                 win_state.browsers[idx].user_input = Some(request.clone());
-                                win_state.urlbar_focused = false;
+                                win_state.focus = Focus::Browser(bid);
                                 let url = ServoUrl::parse(&request).or_else(|error| {
                                     // FIXME: weak
                                     if request.ends_with(".com") || request.ends_with(".org") || request.ends_with(".net") {
@@ -260,15 +670,24 @@ fn main() {
                             WindowCommand::ToggleOptionShowLogs => {
                                 win_state.logs_visible = !win_state.logs_visible;
                             },
+                            WindowCommand::ToggleOptionSmoothScrolling => {
+                                win_state.smooth_scrolling = !win_state.smooth_scrolling;
+                            },
+                            WindowCommand::ToggleOptionKineticScrolling => {
+                                win_state.kinetic_scrolling = !win_state.kinetic_scrolling;
+                            },
                             WindowCommand::NewTab => {
-                                let browser = servo.new_browser("about:blank");
+                                let newtab_url = ServoUrl::parse(pages::NEWTAB_URL).unwrap();
+                                let newtab = pages::render(&newtab_url, &win_state, &page_context);
+                                let url = newtab.map_or("about:blank".to_owned(), |url| url.into_string());
+                                let browser = servo.new_browser(&url);
                                 servo.select_browser(browser.id);
                                 servo.update_geometry(view.get_geometry());
-                                win_state.current_browser_index = Some(idx + 1);
+                                win_state.current_browser_index = Some(win_state.browsers.len());
                                 win_state.browsers.push(browser);
                                 if cfg!(all(not(feature = "force-glutin"), target_os = "macos")) {
                                     // Focus urlbar, but only on cocoa
-                                    win_state.urlbar_focused = true;
+                                    win_state.focus = Focus::Urlbar;
                                 }
                             },
                             WindowCommand::CloseTab => {
@@ -282,9 +701,40 @@ fn main() {
                                     };
                                     servo.select_browser(new_id);
                                     servo.close_browser(id);
-                                    win_state.browsers.remove(idx);
+                                    let browser = win_state.browsers.remove(idx);
+                                    if let Some(url) = browser.url {
+                                        win_state.push_closed_tab(ClosedTab {
+                                            index: idx,
+                                            url,
+                                            title: browser.title,
+                                            zoom: browser.zoom,
+                                        });
+                                    }
                                 }
                             },
+                            WindowCommand::ReopenClosedTab => {
+                                if let Some(tab) = win_state.closed_tabs.pop() {
+                                    // Servo can't restore the session history. Only the
+                                    // last URL is loaded, and the history starts over with
+                                    // the first HistoryChanged. Loaded after creation to go
+                                    // through allow_navigation.
+                                    let mut browser = servo.new_browser("about:blank");
+                                    servo.select_browser(browser.id);
+                                    servo.update_geometry(view.get_geometry());
+                                    match ServoUrl::parse(&tab.url) {
+                                        Ok(url) => servo.load_url(browser.id, url),
+                                        Err(err) => warn!("Can't parse url: {}", err),
+                                    }
+                                    if tab.zoom != 1.0 {
+                                        servo.zoom(tab.zoom);
+                                    }
+                                    browser.title = tab.title;
+                                    browser.zoom = tab.zoom;
+                                    let index = cmp::min(tab.index, win_state.browsers.len());
+                                    win_state.browsers.insert(index, browser);
+                                    win_state.current_browser_index = Some(index);
+                                }
+                            }
                             WindowCommand::PrevTab => {
                                 let new_idx = if idx == 0 {
                                     win_state.browsers.len() - 1
@@ -315,7 +765,13 @@ fn main() {
                             WindowCommand::ToggleOptionFragmentBorders => { },
                             WindowCommand::ToggleOptionParallelDisplayListBuidling => { },
                             WindowCommand::ToggleOptionShowParallelLayout => { },
-                            WindowCommand::ToggleOptionConvertMouseToTouch => { },
+                            WindowCommand::ToggleOptionConvertMouseToTouch => {
+                                win_state.debug_options.convert_mouse_to_touch = !win_state.debug_options.convert_mouse_to_touch;
+                                if mouse_touch_down {
+                                    servo.perform_touch(MOUSE_TOUCH_ID, TouchPhase::Cancelled, pointer.0, pointer.1);
+                                    mouse_touch_down = false;
+                                }
+                            },
                             WindowCommand::ToggleOptionTileBorders => { },
 
                             WindowCommand::ToggleOptionWRProfiler => {
@@ -337,37 +793,267 @@ fn main() {
                 }
             }
 
+            if let Some(ref mut kiosk) = kiosk {
+                let input = view_events.iter().any(|event| {
+                    match *event {
+                        ViewEvent::GeometryDidChange => false,
+                        _ => true,
+                    }
+                });
+                if input {
+                    kiosk.record_activity();
+                }
+            }
+
+            let zoom_allowed = kiosk.as_ref().map_or(true, |kiosk| kiosk.allows(&WindowCommand::ZoomIn, &win_state));
+
             for event in view_events {
                 let idx = win_state.current_browser_index.unwrap();
-                let state = &mut win_state.browsers[idx];
+                let bid = win_state.browsers[idx].id;
                 match event {
                     ViewEvent::GeometryDidChange => {
                         servo.update_geometry(view.get_geometry());
                         view.update_drawable();
                     }
                     ViewEvent::MouseWheel(delta, phase) => {
-                        // FIXME: magic value
-                        static LINE_HEIGHT: f32 = 38.0;
-                        let (mut x, mut y) = match delta {
-                            MouseScrollDelta::PixelDelta(x, y) => (x, y),
-                            MouseScrollDelta::LineDelta(x, y) => (x, y * LINE_HEIGHT),
-                        };
-                        if y.abs() >= x.abs() { x = 0.0; } else { y = 0.0; }
-                        servo.perform_scroll(0, 0, x, y, phase);
+                        if ctrl_down && zoom_allowed {
+                            win_state.browsers[idx].zoom *= scroll::zoom_factor(&delta);
+                            servo.zoom(win_state.browsers[idx].zoom);
+                            zoom_changed = true;
+                            continue;
+                        }
+                        let (x, y) = scroller.scroll(delta, &phase, pointer,
+                                                     win_state.smooth_scrolling,
+                                                     win_state.kinetic_scrolling);
+                        if x != 0.0 || y != 0.0 {
+                            servo.perform_scroll(pointer.0, pointer.1, x, y, phase);
+                        }
+                    }
+                    ViewEvent::Pinch(magnification) => {
+                        if zoom_allowed {
+                            let browser = &mut win_state.browsers[idx];
+                            let pinch_zoom = (browser.pinch_zoom * magnification)
+                                .max(MIN_PINCH_ZOOM).min(MAX_PINCH_ZOOM);
+                            servo.pinch_zoom(pinch_zoom / browser.pinch_zoom);
+                            browser.pinch_zoom = pinch_zoom;
+                            zoom_changed = true;
+                        }
                     }
                     ViewEvent::MouseMoved(x, y) => {
-                        servo.perform_mouse_move(x, y);
+                        pointer = (x, y);
+                        if mouse_touch_down {
+                            servo.perform_touch(MOUSE_TOUCH_ID, TouchPhase::Moved, x, y);
+                        } else {
+                            servo.perform_mouse_move(x, y);
+                        }
+                    }
+                    ViewEvent::Touch(id, phase, x, y) => {
+                        if let TouchPhase::Started = phase {
+                            win_state.context_menu = None;
+                            win_state.focus = Focus::Browser(bid);
+                            scroller.stop();
+                        }
+                        servo.perform_touch(id, phase, x, y);
                     }
                     ViewEvent::MouseInput(element_state, button, x, y) => {
+                        // Left button drags become single finger touches
+                        let pressed = element_state == ElementState::Pressed;
+                        if button == MouseButton::Left &&
+                           (mouse_touch_down || (pressed && win_state.debug_options.convert_mouse_to_touch)) {
+                            let phase = if pressed { TouchPhase::Started } else { TouchPhase::Ended };
+                            if pressed {
+                                win_state.context_menu = None;
+                                win_state.focus = Focus::Browser(bid);
+                                scroller.stop();
+                            }
+                            mouse_touch_down = pressed;
+                            servo.perform_touch(MOUSE_TOUCH_ID, phase, x, y);
+                            continue;
+                        }
+                        if element_state == ElementState::Pressed {
+                            scroller.stop();
+                            let tabs_allowed = kiosk.as_ref().map_or(true, |kiosk| kiosk.allows(&WindowCommand::NewTab, &win_state));
+                            let new_tab = tabs_allowed && (button == MouseButton::Middle ||
+                                                           (button == MouseButton::Left && cmd_or_ctrl_down));
+                            // Only a click on the hovered link opens a new tab
+                            let link = win_state.status.as_ref().and_then(|status| ServoUrl::parse(status).ok());
+                            match link {
+                                Some(ref link) if new_tab => servo.open_next_navigation_in_new_tab(bid, link.clone()),
+                                _ => servo.cancel_new_tab_unless_over(None),
+                            }
+                            win_state.context_menu = if button == MouseButton::Right {
+                                let mut items = context_menu(&win_state);
+                                if let Some(ref kiosk) = kiosk {
+                                    items.retain(|item| kiosk.allows(&item.command, &win_state));
+                                }
+                                Some(items)
+                            } else {
+                                None
+                            };
+                            win_state.focus = if win_state.context_menu.is_some() {
+                                Focus::Overlay
+                            } else {
+                                Focus::Browser(bid)
+                            };
+                        } else {
+                            let link = win_state.status.as_ref().and_then(|status| ServoUrl::parse(status).ok());
+                            servo.cancel_new_tab_unless_over(link.as_ref());
+                        }
                         servo.perform_click(x, y, element_state, button);
                     }
                     ViewEvent::KeyEvent(c, key, keystate, modifiers) => {
-                        servo.send_key(state.id, c, key, keystate, modifiers);
+                        // Only the focused browser gets the key. A key
+                        // pressed in the context menu closes it.
+                        let focused_browser = match win_state.focus {
+                            Focus::Browser(id) => Some(id),
+                            Focus::Urlbar | Focus::Overlay => None,
+                        };
+                        if keystate == KeyState::Pressed {
+                            win_state.context_menu = None;
+                            scroller.stop();
+                        }
+                        let cmd_or_ctrl = if cfg!(target_os = "macos") {
+                            key == Key::LeftSuper || key == Key::RightSuper
+                        } else {
+                            key == Key::LeftControl || key == Key::RightControl
+                        };
+                        if cmd_or_ctrl {
+                            cmd_or_ctrl_down = keystate != KeyState::Released;
+                        }
+                        if key == Key::LeftControl || key == Key::RightControl {
+                            ctrl_down = keystate != KeyState::Released;
+                        }
+                        // Keys can be sent twice (without and with their
+                        // character). Only run the shortcut once.
+                        if let Some(id) = focused_browser {
+                            let timeout = Duration::from_millis(SHORTCUT_TIMEOUT_MS);
+                            pending_shortcuts.retain(|&(_, _, time)| time.elapsed() < timeout);
+                            if keystate != KeyState::Released &&
+                               shortcuts::command(key, modifiers).is_some() &&
+                               !pending_shortcuts.iter().any(|&(k, m, _)| (k, m) == (key, modifiers)) {
+                                pending_shortcuts.push((key, modifiers, Instant::now()));
+                            }
+                            servo.send_key(id, c, key, keystate, modifiers);
+                        }
+                    }
+                }
+            }
+
+            for event in timer_events {
+                match event {
+                    TimerEvent::LoadTimeout(id) => {
+                        load_timers.remove(&id);
+                        let loading = win_state.browsers.iter().any(|b| b.id == id && b.is_loading);
+                        if loading {
+                            show_load_error(&servo, &mut win_state, &page_context, id, LoadErrorKind::Timeout);
+                        }
+                    }
+                    TimerEvent::KioskIdle => {
+                        let kiosk = kiosk.as_mut().unwrap();
+                        let timeout = kiosk.idle_timeout.unwrap();
+                        let idle_time = kiosk.idle_time();
+                        if idle_time >= timeout {
+                            info!("Idle for {}s, going home", idle_time.as_secs());
+                            let bid = win_state.browsers[win_state.current_browser_index.unwrap()].id;
+                            servo.load_url(bid, ServoUrl::parse(kiosk.go_home()).unwrap());
+                            timers.schedule(timeout, TimerEvent::KioskIdle);
+                        } else {
+                            timers.schedule(timeout - idle_time, TimerEvent::KioskIdle);
+                        }
+                    }
+                    TimerEvent::KioskRotate => {
+                        // Don't rotate while someone is using the browser
+                        let kiosk = kiosk.as_mut().unwrap();
+                        let interval = kiosk.rotate_interval.unwrap();
+                        if kiosk.idle_time() >= interval {
+                            let bid = win_state.browsers[win_state.current_browser_index.unwrap()].id;
+                            servo.load_url(bid, ServoUrl::parse(kiosk.next_url()).unwrap());
+                        }
+                        timers.schedule(interval, TimerEvent::KioskRotate);
+                    }
+                    TimerEvent::HideZoomIndicator => {
+                        zoom_indicator_timer = None;
+                        win_state.zoom_indicator = None;
+                    }
+                    TimerEvent::ReplayNext => {
+                        replay_timer_scheduled = false;
+                    }
+                    TimerEvent::BenchmarkNext => {
+                        benchmark_timer = None;
+                        let benchmark = benchmark.as_mut().unwrap();
+                        let browser = &mut win_state.browsers[0];
+                        match browser.load_error {
+                            Some(ref error) => {
+                                benchmark.finish(None, None, Some(format!("{:?}", error.kind)));
+                            }
+                            None => benchmark.finish(browser.url.clone(), browser.title.clone(), None),
+                        }
+                        match benchmark.start_next() {
+                            Some(url) => {
+                                browser.load_error = None;
+                                servo.load_url(browser.id, ServoUrl::parse(&url).unwrap());
+                                benchmark_timer = Some(timers.schedule(benchmark_timeout, TimerEvent::BenchmarkNext));
+                            }
+                            None => {
+                                let report = options.benchmark_report.as_ref().map(|path| path.as_path());
+                                let code = match benchmark.write_report(report, logs.stdout()) {
+                                    Ok(()) if benchmark.has_failures() => 1,
+                                    Ok(()) => 0,
+                                    Err(err) => {
+                                        eprintln!("{}: Can't write the benchmark report: {}", PKG_NAME, err);
+                                        1
+                                    }
+                                };
+                                exit_code.set(code);
+                                app.quit();
+                            }
+                        }
+                    }
+                    TimerEvent::ScrollFrame => {
+                        scroll_frame_scheduled = false;
+                        let (x, y) = scroller.frame();
+                        if x != 0.0 || y != 0.0 {
+                            let (px, py) = scroller.position();
+                            servo.perform_scroll(px, py, x, y, TouchPhase::Moved);
+                        }
                     }
                 }
             }
 
+            if scroller.is_animating() && !scroll_frame_scheduled {
+                timers.schedule(Duration::from_millis(scroll::FRAME_MS), TimerEvent::ScrollFrame);
+                scroll_frame_scheduled = true;
+            }
+
+            if let Some(delay) = replayer.as_ref().and_then(|replayer| replayer.next_delay()) {
+                if !replay_timer_scheduled {
+                    timers.schedule(delay, TimerEvent::ReplayNext);
+                    replay_timer_scheduled = true;
+                }
+            }
+
+            if zoom_changed {
+                let zoom = {
+                    let browser = &win_state.browsers[win_state.current_browser_index.unwrap()];
+                    browser.zoom * browser.pinch_zoom
+                };
+                win_state.zoom_indicator = Some(format!("Zoom: {}%", (zoom * 100.0).round()));
+                if let Some(timer) = zoom_indicator_timer.take() {
+                    timers.cancel(timer);
+                }
+                let delay = Duration::from_millis(ZOOM_INDICATOR_MS);
+                zoom_indicator_timer = Some(timers.schedule(delay, TimerEvent::HideZoomIndicator));
+            }
+
             for event in servo_events {
+                // Servo can create browsers on its own (WebDriver's New Window
+                // for example). Adopt them as new tabs.
+                if let Some(id) = event.browser_id() {
+                    if !win_state.browsers.iter().any(|b| b.id == id) {
+                        info!("Adopting new browser: {:?}", id);
+                        win_state.browsers.push(BrowserState::new(id));
+                    }
+                }
                 match event {
                     ServoEvent::SetWindowInnerSize(..) => {
                         // ignore
@@ -397,26 +1083,60 @@ fn main() {
                         match win_state.browsers.iter_mut().find(|b| b.id == id) {
                             Some(browser) => {
                                 browser.is_loading = true;
+                                perf.load_start(id, browser.requested_url.clone());
                             }
                             None => warn!("Got message for unkown browser:  {:?}", id)
                         }
+                        let timeout = Duration::from_secs(LOAD_TIMEOUT_SECS);
+                        let timer = timers.schedule(timeout, TimerEvent::LoadTimeout(id));
+                        if let Some(previous) = load_timers.insert(id, timer) {
+                            timers.cancel(previous);
+                        }
                     }
                     ServoEvent::LoadEnd(id) => {
                         match win_state.browsers.iter_mut().find(|b| b.id == id) {
                             Some(browser) => {
                                 browser.is_loading = false;
+                                browser.requested_url = None;
+                                perf.load_end(id, browser.url.clone());
                             }
                             None => warn!("Got message for unkown browser:  {:?}", id)
                         }
+                        if let Some(timer) = load_timers.remove(&id) {
+                            timers.cancel(timer);
+                        }
+                        if let Some(ref mut benchmark) = benchmark {
+                            // The page is given some time to settle before the next load
+                            if id == win_state.browsers[0].id && benchmark.load_end() {
+                                if let Some(timer) = benchmark_timer.take() {
+                                    timers.cancel(timer);
+                                }
+                                benchmark_timer = Some(timers.schedule(benchmark.settle, TimerEvent::BenchmarkNext));
+                            }
+                        }
                     }
-                    ServoEvent::HeadParsed(..) => {
-                        // FIXME
+                    ServoEvent::HeadParsed(id) => {
+                        perf.head_parsed(id);
+                        if let Some(ref mut benchmark) = benchmark {
+                            if id == win_state.browsers[0].id {
+                                benchmark.head_parsed();
+                            }
+                        }
+                        // The server responded
+                        if let Some(timer) = load_timers.remove(&id) {
+                            timers.cancel(timer);
+                        }
                     }
                     ServoEvent::HistoryChanged(id, entries, current) => {
                         match win_state.browsers.iter_mut().find(|b| b.id == id) {
                             Some(browser) => {
                                 let url = entries[current].url.to_string();
+                                if !pages::is_error_page(&url, &profile) {
+                                    browser.load_error = None;
+                                }
                                 browser.url = Some(url);
+                                browser.history = entries.iter().map(|entry| entry.url.to_string()).collect();
+                                browser.history_index = current;
                                 browser.can_go_back = current > 0;
                                 browser.can_go_forward = current < entries.len() - 1;
                             }
@@ -429,15 +1149,105 @@ fn main() {
                     ServoEvent::FaviconChanged(..) => {
                         // FIXME
                     }
-                    ServoEvent::Key(..) => {
-                        // FIXME
+                    ServoEvent::Key(_, key, mods) => {
+                        // The page didn't consume the key
+                        let timeout = Duration::from_millis(SHORTCUT_TIMEOUT_MS);
+                        let pending = pending_shortcuts.iter().position(|&(k, m, time)| {
+                            (k, m) == (key, mods) && time.elapsed() < timeout
+                        });
+                        if let Some(index) = pending {
+                            pending_shortcuts.remove(index);
+                            if let Some(cmd) = shortcuts::command(key, mods) {
+                                if kiosk.as_ref().map_or(true, |kiosk| kiosk.allows(&cmd, &win_state)) {
+                                    pending_commands.push(cmd);
+                                } else {
+                                    info!("Command disabled in kiosk mode: {:?}", cmd);
+                                }
+                            }
+                        }
                     }
                     ServoEvent::OpenInDefaultBrowser(url) => {
-                        open::that(url).ok();
+                        // The default browser would escape the kiosk
+                        if kiosk.is_none() {
+                            open::that(url).ok();
+                        }
+                    }
+                    ServoEvent::ShellPageRequested(id, url) => {
+                        let from_shell_page = win_state.browsers.iter().find(|b| b.id == id).map_or(false, |b| {
+                            b.url.as_ref().map_or(false, |url| pages::is_shell_page(url, &profile))
+                        });
+                        let command = if from_shell_page { pages::command(&url) } else { None };
+                        let command = command.and_then(|cmd| {
+                            if kiosk.as_ref().map_or(true, |kiosk| kiosk.allows(&cmd, &win_state)) {
+                                Some(cmd)
+                            } else {
+                                info!("Command disabled in kiosk mode: {:?}", cmd);
+                                None
+                            }
+                        });
+                        if let Some(cmd) = command {
+                            pending_commands.push(cmd);
+                            if pages::page_path(&url) == Some("settings") {
+                                // Show the updated settings
+                                servo.load_url(id, ServoUrl::parse("servoshell://settings").unwrap());
+                            }
+                        } else if let Some(page_url) = pages::render(&url, &win_state, &page_context) {
+                            servo.load_url(id, page_url);
+                        }
+                    }
+                    ServoEvent::LoadRequested(id, url) => {
+                        if let Some(browser) = win_state.browsers.iter_mut().find(|b| b.id == id) {
+                            browser.requested_url = Some(url.into_string());
+                        }
+                    }
+                    ServoEvent::LoadInBackgroundTab(url) => {
+                        let current = win_state.browsers[win_state.current_browser_index.unwrap()].id;
+                        let browser = servo.new_browser(url.as_str());
+                        servo.select_browser(current);
+                        win_state.browsers.push(browser);
+                    }
+                    ServoEvent::NavigationBlocked(id, url, rule) => {
+                        warn!("Navigation to {} blocked by {}", url, rule);
+                        let count = match win_state.browsers.iter_mut().find(|b| b.id == id) {
+                            Some(browser) => {
+                                browser.blocked_navigations += 1;
+                                browser.blocked_navigations
+                            }
+                            None => 1,
+                        };
+                        let blocked_url = pages::blocked_url(&url, &rule, count);
+                        if let Some(page_url) = pages::render(&blocked_url, &win_state, &page_context) {
+                            servo.load_url(id, page_url);
+                        }
+                    }
+                    ServoEvent::LoadFailed(id, kind) => {
+                        show_load_error(&servo, &mut win_state, &page_context, id, kind);
                     }
                 }
             }
 
+            // Focus follows the current tab, and goes back to it when the
+            // context menu closes
+            if let Some(idx) = win_state.current_browser_index {
+                let current = win_state.browsers[idx].id;
+                let focus = win_state.focus;
+                match focus {
+                    Focus::Browser(id) if id != current => {
+                        win_state.focus = Focus::Browser(current);
+                    }
+                    Focus::Overlay if win_state.context_menu.is_none() => {
+                        win_state.focus = Focus::Browser(current);
+                    }
+                    _ => {}
+                }
+            }
+            if win_state.focus != before_win_state.focus {
+                match win_state.focus {
+                    Focus::Browser(id) => servo.focus(id),
+                    Focus::Urlbar | Focus::Overlay => servo.blur(),
+                }
+            }
+
             let app_has_changed = before_app_state == app_state;
             let win_has_changed = before_win_state == win_state;
             if app_has_changed || win_has_changed {
@@ -461,4 +1271,78 @@ fn main() {
 
     app.run(handle_events);
 
+    logs.stop_capture();
+    exit_code.get()
+}
+
+// URLs given on the command line can also be paths to local files
+// or hosts without a scheme.
+fn url_from_arg(arg: &str) -> String {
+    let path = Path::new(arg);
+    if path.exists() {
+        let path = env::current_dir().unwrap().join(path);
+        if let Ok(url) = ServoUrl::from_file_path(&path) {
+            return url.into_string();
+        }
+    }
+    // "localhost:8000" parses, with "localhost" as the scheme
+    match ServoUrl::parse(arg) {
+        Ok(ref url) if arg.contains("://") || KNOWN_SCHEMES.contains(&url.scheme()) => url.as_str().to_owned(),
+        _ => format!("http://{}", arg),
+    }
+}
+
+// The page actions, and the link actions if a link is hovered.
+fn context_menu(win_state: &WindowState) -> Vec<ContextMenuItem> {
+    let browser = &win_state.browsers[win_state.current_browser_index.unwrap()];
+    let mut items = vec![];
+    let item = |label, command| ContextMenuItem { label, command };
+
+    if let Some(ref link) = win_state.status {
+        if ServoUrl::parse(link).is_ok() {
+            items.push(item("Open Link in New Tab", WindowCommand::OpenInNewTab(link.clone())));
+            items.push(item("Copy Link Address", WindowCommand::CopyLink(link.clone())));
+        }
+    }
+    if browser.can_go_back {
+        items.push(item("Back", WindowCommand::NavigateBack));
+    }
+    if browser.can_go_forward {
+        items.push(item("Forward", WindowCommand::NavigateForward));
+    }
+    items.push(item("Reload", WindowCommand::Reload));
+    if browser.url.is_some() {
+        items.push(item("Open in Default Browser", WindowCommand::OpenInDefaultBrowser));
+        items.push(item("Copy Page URL", WindowCommand::CopyUrl));
+    }
+    items.push(item("Zoom In", WindowCommand::ZoomIn));
+    items.push(item("Zoom Out", WindowCommand::ZoomOut));
+    items.push(item("Actual Size", WindowCommand::ZoomToActualSize));
+    items.push(item("Open Logs", WindowCommand::OpenInNewTab(format!("{}logs", pages::SCHEME_PREFIX))));
+    items
+}
+
+// Record the failure and replace the page with an error page. Servo's
+// certificate error page stays: it lets the user proceed anyway.
+fn show_load_error(servo: &Servo,
+                   win_state: &mut WindowState,
+                   page_context: &PageContext,
+                   id: BrowserId,
+                   kind: LoadErrorKind) {
+    let error = match win_state.browsers.iter_mut().find(|b| b.id == id) {
+        Some(browser) => {
+            let url = browser.requested_url.clone().or(browser.url.clone()).unwrap_or(String::new());
+            warn!("Failed to load {}: {:?}", url, kind);
+            let error = LoadError { kind: kind, url: url };
+            browser.load_error = Some(error.clone());
+            error
+        }
+        None => return,
+    };
+    if error.kind == LoadErrorKind::Certificate {
+        return;
+    }
+    if let Some(page_url) = pages::render(&pages::error_url(&error), win_state, page_context) {
+        servo.load_url(id, page_url);
+    }
 }
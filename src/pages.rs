@@ -0,0 +1,511 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! HTML pages generated by the shell (servoshell://…).
+//! Pages are written to the profile directory and loaded as file: URLs.
+
+use log::LogLevel;
+use logs::{ShellLog, ShellLogs};
+use perf::{self, Perf};
+use profile::Profile;
+use servo::ServoUrl;
+use state::{LoadError, LoadErrorKind, WindowState};
+use std::cmp;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+use traits::window::WindowCommand;
+
+pub const SCHEME_PREFIX: &'static str = "servoshell://";
+pub const NEWTAB_URL: &'static str = "servoshell://newtab";
+
+/// What pages are generated from, besides the window state.
+pub struct PageContext<'a> {
+    pub logs: &'a ShellLogs,
+    pub perf: &'a Perf,
+    pub profile: &'a Profile,
+    pub resources_path: &'a Path,
+    pub servo_version: &'a str,
+}
+
+pub enum PageHandler {
+    /// HTML generated by the shell
+    Html(fn(&ServoUrl, &WindowState, &PageContext) -> String),
+    /// Alias to a web page, opened in the default browser
+    External(&'static str),
+    /// Link to a shell command
+    Command(WindowCommand),
+}
+
+pub struct Page {
+    pub path: &'static str,
+    pub handler: PageHandler,
+}
+
+const PAGES: &'static [Page] = &[
+    Page { path: "about", handler: PageHandler::Html(about) },
+    Page { path: "settings", handler: PageHandler::Html(settings) },
+    Page { path: "newtab", handler: PageHandler::Html(newtab) },
+    Page { path: "logs", handler: PageHandler::Html(logs) },
+    Page { path: "console", handler: PageHandler::Html(logs) },
+    Page { path: "perf", handler: PageHandler::Html(perf) },
+    Page { path: "error", handler: PageHandler::Html(error) },
+    Page { path: "blocked", handler: PageHandler::Html(blocked) },
+    Page { path: "retry", handler: PageHandler::Command(WindowCommand::Reload) },
+    Page { path: "reopen", handler: PageHandler::Command(WindowCommand::ReopenClosedTab) },
+    Page {
+        path: "issue/servoshell",
+        handler: PageHandler::External("http://github.com/paulrouget/servoshell/issues/new"),
+    },
+    Page {
+        path: "issue/servo",
+        handler: PageHandler::External("http://github.com/servo/servo/issues/new"),
+    },
+];
+
+// Options that can be toggled from servoshell://settings
+const SETTINGS: &'static [(&'static str, &'static str, WindowCommand)] = &[
+    ("logs", "Show logs", WindowCommand::ToggleOptionShowLogs),
+    ("smooth_scrolling", "Smooth scrolling", WindowCommand::ToggleOptionSmoothScrolling),
+    ("kinetic_scrolling", "Kinetic scrolling", WindowCommand::ToggleOptionKineticScrolling),
+    ("mouse_to_touch", "Convert mouse to touch", WindowCommand::ToggleOptionConvertMouseToTouch),
+    ("wr_profiler", "WebRender profiler", WindowCommand::ToggleOptionWRProfiler),
+    ("wr_texture_cache_debug", "WebRender texture cache debug", WindowCommand::ToggleOptionWRTextureCacheDebug),
+    ("wr_render_target_debug", "WebRender render target debug", WindowCommand::ToggleOptionWRTargetDebug),
+];
+
+/// The registered page for this servoshell:// URL.
+pub fn find(url: &ServoUrl) -> Option<&'static Page> {
+    page_path(url).and_then(|path| PAGES.iter().find(|page| page.path == path))
+}
+
+/// Generate the page for this servoshell:// URL, and return the URL to
+/// load instead. Unknown pages get a "not found" page.
+pub fn render(url: &ServoUrl, state: &WindowState, context: &PageContext) -> Option<ServoUrl> {
+    let html = match find(url).map(|page| &page.handler) {
+        Some(&PageHandler::Html(handler)) => handler(url, state, context),
+        Some(_) => return None,
+        None => not_found(url),
+    };
+    let name = page_path(url).unwrap_or("").replace('/', "-");
+    write_page(context.profile, &name, &html)
+}
+
+/// The command a servoshell:// link maps to. servoshell://settings?toggle=NAME
+/// toggles one of the settings. Only links from generated pages run
+/// commands: web pages must not control the shell.
+pub fn command(url: &ServoUrl) -> Option<WindowCommand> {
+    if let Some(&PageHandler::Command(ref cmd)) = find(url).map(|page| &page.handler) {
+        return Some(cmd.clone());
+    }
+    if page_path(url) != Some("settings") {
+        return None;
+    }
+    query_param(url, "toggle").and_then(|name| {
+        SETTINGS.iter().find(|&&(n, _, _)| n == name).map(|&(_, _, ref cmd)| cmd.clone())
+    })
+}
+
+/// The error page describing this failure.
+pub fn error_url(error: &LoadError) -> ServoUrl {
+    let url = format!("{}error?kind={}&url={}",
+                      SCHEME_PREFIX, error_kind_name(&error.kind), percent_encode(&error.url));
+    ServoUrl::parse(&url).unwrap()
+}
+
+/// The page explaining why the navigation to `url` was denied.
+pub fn blocked_url(url: &ServoUrl, rule: &str, count: u32) -> ServoUrl {
+    let url = format!("{}blocked?url={}&rule={}&count={}",
+                      SCHEME_PREFIX, percent_encode(url.as_str()), percent_encode(rule), count);
+    ServoUrl::parse(&url).unwrap()
+}
+
+/// Whether this is the file: URL of a generated page.
+pub fn is_shell_page(url: &str, profile: &Profile) -> bool {
+    url.starts_with(&pages_dir_url(profile))
+}
+
+/// Whether this is the file: URL of a generated error page.
+pub fn is_error_page(url: &str, profile: &Profile) -> bool {
+    url == format!("{}error.html", pages_dir_url(profile))
+}
+
+const STYLE: &'static str = "
+  body { margin: 50px; font-family: Helvetica; }
+  body, h1 { font-weight: lighter; }
+  table { border-collapse: collapse; width: 100%; font-family: Menlo, monospace; font-size: 12px; }
+  td { padding: 2px 8px; vertical-align: top; border-bottom: 1px solid #EEE; }
+  .ERROR { color: #C00; }
+  .retry { display: inline-block; padding: 6px 16px; border: 1px solid #AAA; border-radius: 4px; color: inherit; text-decoration: none; }
+  .WARN { color: #B60; }
+  .fps { display: flex; align-items: flex-end; height: 60px; border-bottom: 1px solid #AAA; }
+  .fps span { width: 8px; margin-right: 1px; background: #888; }
+";
+
+/// The path of a servoshell:// URL, without query and fragment.
+/// `servoshell://logs?level=warn` → `logs`
+pub fn page_path(url: &ServoUrl) -> Option<&str> {
+    let url = url.as_str();
+    if !url.starts_with(SCHEME_PREFIX) {
+        return None;
+    }
+    let path = &url[SCHEME_PREFIX.len()..];
+    let end = path.find(|c| c == '?' || c == '#').unwrap_or(path.len());
+    Some(path[..end].trim_right_matches('/'))
+}
+
+/// Value of a query parameter, percent-decoded.
+pub fn query_param(url: &ServoUrl, name: &str) -> Option<String> {
+    let query = match url.query() {
+        Some(query) => query,
+        None => return None,
+    };
+    query.split('&').filter_map(|pair| {
+        let mut pair = pair.splitn(2, '=');
+        match (pair.next(), pair.next()) {
+            (Some(key), value) if key == name => Some(percent_decode(value.unwrap_or(""))),
+            _ => None,
+        }
+    }).next()
+}
+
+fn percent_encode(input: &str) -> String {
+    let mut encoded = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        match byte {
+            b'A'...b'Z' | b'a'...b'z' | b'0'...b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn percent_decode(input: &str) -> String {
+    let mut bytes = Vec::with_capacity(input.len());
+    let mut iter = input.bytes();
+    while let Some(byte) = iter.next() {
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex: Vec<u8> = iter.clone().take(2).collect();
+                let decoded = String::from_utf8(hex).ok().and_then(|hex| {
+                    u8::from_str_radix(&hex, 16).ok()
+                });
+                match decoded {
+                    Some(decoded) => {
+                        bytes.push(decoded);
+                        iter.next();
+                        iter.next();
+                    }
+                    None => bytes.push(b'%'),
+                }
+            }
+            _ => bytes.push(byte),
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn document(title: &str, body: &str) -> String {
+    format!("<!DOCTYPE html>\n<meta charset=\"utf8\">\n<title>{}</title>\n<style>{}</style>\n{}",
+            escape(title), STYLE, body)
+}
+
+/// The file: URL of the directory generated pages are written to.
+pub fn pages_dir_url(profile: &Profile) -> String {
+    match ServoUrl::from_file_path(&profile.pages_dir()) {
+        Ok(url) => format!("{}/", url.as_str().trim_right_matches('/')),
+        Err(_) => String::new(),
+    }
+}
+
+/// Write the page to the profile's pages directory, and return its URL.
+/// The page is written to a new file first, then renamed, so a page
+/// being loaded is never half written.
+fn write_page(profile: &Profile, name: &str, html: &str) -> Option<ServoUrl> {
+    let dir = profile.pages_dir();
+    let path = dir.join(format!("{}.html", name));
+    let temp_path = dir.join(format!("{}.html.tmp", name));
+    let result = profile.create_pages_dir().and_then(|_| {
+        // Left over by a crash
+        fs::remove_file(&temp_path).ok();
+        OpenOptions::new().write(true).create_new(true).open(&temp_path)
+    }).and_then(|mut file| {
+        file.write_all(html.as_bytes())
+    }).and_then(|_| {
+        fs::rename(&temp_path, &path)
+    });
+    match result {
+        Ok(_) => ServoUrl::from_file_path(&path).ok(),
+        Err(err) => {
+            warn!("Can't write {:?}: {}", path, err);
+            None
+        }
+    }
+}
+
+/// servoshell://logs and servoshell://console.
+/// Optional parameters: `level` (show this level and more severe ones)
+/// and `target` (substring of the log target).
+fn logs(url: &ServoUrl, _state: &WindowState, context: &PageContext) -> String {
+    let logs: Vec<ShellLog> = context.logs.get_buffered_logs();
+    let console = page_path(url) == Some("console");
+    let level = query_param(url, "level").and_then(|l| LogLevel::from_str(&l).ok());
+    let target = query_param(url, "target").unwrap_or(String::new());
+
+    let rows: String = logs.iter().filter(|log| {
+        (!console || log.is_console()) &&
+        level.map_or(true, |level| log.level <= level) &&
+        log.target.contains(target.as_str())
+    }).map(|log| {
+        format!("<tr class=\"{}\"><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                log.level, log.level, escape(&log.target), escape(&log.message))
+    }).collect();
+
+    let (title, action) = if console {
+        ("Console", "servoshell://console")
+    } else {
+        ("Logs", "servoshell://logs")
+    };
+
+    let options: String = ["", "error", "warn", "info"].iter().map(|l| {
+        let selected = level.map_or(l.is_empty(), |level| level.to_string().to_lowercase() == *l);
+        format!("<option value=\"{}\"{}>{}</option>",
+                l, if selected { " selected" } else { "" }, if l.is_empty() { "all" } else { l })
+    }).collect();
+
+    let body = format!("<h1>{title}</h1>
+<form action=\"{action}\" method=\"get\">
+  Level <select name=\"level\">{options}</select>
+  Target <input name=\"target\" value=\"{target}\">
+  <button>Filter</button>
+  <a href=\"{other}\">{other_label}</a>
+</form>
+<table>
+{rows}</table>",
+        title = title,
+        action = action,
+        options = options,
+        target = escape(&target),
+        other = if console { "servoshell://logs" } else { "servoshell://console" },
+        other_label = if console { "All logs" } else { "Console only" },
+        rows = rows);
+
+    document(title, &body)
+}
+
+/// servoshell://perf
+fn perf(_url: &ServoUrl, state: &WindowState, context: &PageContext) -> String {
+    let ms = |duration: Option<Duration>| duration.map_or("-".to_owned(), |d| format!("{} ms", perf::millis(d)));
+    let rows: String = context.perf.get_loads().iter().rev().map(|load| {
+        let tab = state.browsers.iter().position(|b| b.id == load.browser)
+            .map_or("closed".to_owned(), |index| (index + 1).to_string());
+        format!("<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                tab,
+                escape(load.url.as_ref().map_or("", |u| u.as_str())),
+                ms(load.head_parsed),
+                ms(load.load_end),
+                ms(load.first_frame))
+    }).collect();
+
+    let fps = context.perf.get_fps_history();
+    let bars: String = fps.iter().map(|fps| {
+        format!("<span style=\"height: {}px\" title=\"{} fps\"></span>", cmp::min(*fps, 60), fps)
+    }).collect();
+    let average = if fps.is_empty() {
+        0
+    } else {
+        fps.iter().sum::<u32>() / fps.len() as u32
+    };
+
+    let body = format!("<h1>Performance</h1>
+<p><a href=\"servoshell://perf\">Refresh</a></p>
+<h2>Page loads</h2>
+<table>
+<tr><td>Tab</td><td>URL</td><td>Head parsed</td><td>Load end</td><td>First frame</td></tr>
+{rows}</table>
+<h2>Frames per second</h2>
+<p>Last {seconds} seconds, {average} fps on average</p>
+<div class=\"fps\">{bars}</div>",
+        rows = rows,
+        seconds = fps.len(),
+        average = average,
+        bars = bars);
+
+    document("Performance", &body)
+}
+
+fn not_found(url: &ServoUrl) -> String {
+    let body = format!("<h1>Page not found</h1>\n<p>{} is not a ServoShell page.</p>\n{}",
+                       escape(url.as_str()), links());
+    document("Page not found", &body)
+}
+
+fn links() -> String {
+    let links: Vec<String> = PAGES.iter().map(|page| {
+        format!("<a href=\"{0}{1}\">{1}</a>", SCHEME_PREFIX, page.path)
+    }).collect();
+    format!("<p>{}</p>", links.join(" - "))
+}
+
+fn backend() -> &'static str {
+    if cfg!(feature = "force-gtk") {
+        "gtk"
+    } else if cfg!(all(not(feature = "force-glutin"), target_os = "macos")) {
+        "cocoa"
+    } else {
+        "glutin"
+    }
+}
+
+/// servoshell://about
+fn about(_url: &ServoUrl, _state: &WindowState, context: &PageContext) -> String {
+    let mut features = vec![];
+    if cfg!(feature = "force-glutin") {
+        features.push("force-glutin");
+    }
+    if cfg!(feature = "force-gtk") {
+        features.push("force-gtk");
+    }
+    if cfg!(debug_assertions) {
+        features.push("debug");
+    }
+
+    let rows = [
+        ("ServoShell", format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))),
+        ("Servo", context.servo_version.to_owned()),
+        ("Backend", backend().to_owned()),
+        ("Build features", if features.is_empty() { "none".to_owned() } else { features.join(", ") }),
+        ("Resources path", context.resources_path.display().to_string()),
+        ("Profile", context.profile.dir().display().to_string()),
+        ("Private browsing", context.profile.is_private().to_string()),
+    ];
+    let rows: String = rows.iter().map(|&(name, ref value)| {
+        format!("<tr><td>{}</td><td>{}</td></tr>\n", name, escape(value))
+    }).collect();
+
+    let body = format!("<h1>About ServoShell</h1>\n<table>\n{}</table>\n{}", rows, links());
+    document("About ServoShell", &body)
+}
+
+/// servoshell://settings
+fn settings(_url: &ServoUrl, state: &WindowState, _context: &PageContext) -> String {
+    let rows: String = SETTINGS.iter().map(|&(name, label, ref cmd)| {
+        let enabled = match *cmd {
+            WindowCommand::ToggleOptionShowLogs => state.logs_visible,
+            WindowCommand::ToggleOptionSmoothScrolling => state.smooth_scrolling,
+            WindowCommand::ToggleOptionKineticScrolling => state.kinetic_scrolling,
+            WindowCommand::ToggleOptionConvertMouseToTouch => state.debug_options.convert_mouse_to_touch,
+            WindowCommand::ToggleOptionWRProfiler => state.debug_options.wr_profiler,
+            WindowCommand::ToggleOptionWRTextureCacheDebug => state.debug_options.wr_texture_cache_debug,
+            WindowCommand::ToggleOptionWRTargetDebug => state.debug_options.wr_render_target_debug,
+            _ => false,
+        };
+        format!("<tr><td>{}</td><td>{}</td><td><a href=\"servoshell://settings?toggle={}\">{}</a></td></tr>\n",
+                label, if enabled { "on" } else { "off" }, name, if enabled { "Disable" } else { "Enable" })
+    }).collect();
+
+    let body = format!("<h1>Settings</h1>\n<table>\n{}</table>\n{}", rows, links());
+    document("Settings", &body)
+}
+
+/// servoshell://newtab
+fn newtab(_url: &ServoUrl, state: &WindowState, _context: &PageContext) -> String {
+    let tabs: String = state.browsers.iter().filter_map(|browser| {
+        browser.url.as_ref().map(|url| {
+            let title = browser.title.as_ref().map_or(url.as_str(), |t| t.as_str());
+            format!("<li><a href=\"{}\">{}</a></li>\n", escape(url), escape(title))
+        })
+    }).collect();
+
+    let closed_tabs: String = state.closed_tabs.iter().rev().map(|tab| {
+        let title = tab.title.as_ref().map_or(tab.url.as_str(), |t| t.as_str());
+        format!("<li><a href=\"{}\">{}</a></li>\n", escape(&tab.url), escape(title))
+    }).collect();
+    let closed_tabs = if closed_tabs.is_empty() {
+        String::new()
+    } else {
+        format!("<h2>Recently closed</h2>
+<p><a href=\"servoshell://reopen\">Reopen the last closed tab</a></p>
+<ul>
+{}</ul>
+", closed_tabs)
+    };
+
+    let body = format!("<h1>New Tab</h1>
+<form action=\"https://duckduckgo.com/html/\" method=\"get\">
+  <input name=\"q\" placeholder=\"Search\" autofocus>
+</form>
+<h2>Open tabs</h2>
+<ul>
+{}</ul>
+{}{}", tabs, closed_tabs, links());
+    document("New Tab", &body)
+}
+
+fn error_kind_name(kind: &LoadErrorKind) -> &'static str {
+    match *kind {
+        LoadErrorKind::Network => "network",
+        LoadErrorKind::Certificate => "certificate",
+        LoadErrorKind::NotFound => "not-found",
+        LoadErrorKind::Crash => "crash",
+        LoadErrorKind::Timeout => "timeout",
+    }
+}
+
+/// servoshell://error?kind=KIND&url=URL
+fn error(url: &ServoUrl, _state: &WindowState, _context: &PageContext) -> String {
+    let failed_url = query_param(url, "url").unwrap_or(String::new());
+    let (title, description) = match query_param(url, "kind").as_ref().map(|k| k.as_str()) {
+        Some("network") => ("Unable to connect",
+                            "The server can't be reached, or the connection was interrupted."),
+        Some("certificate") => ("Insecure connection",
+                                "The certificate of this site is not valid."),
+        Some("not-found") => ("File not found",
+                              "The requested file doesn't exist."),
+        Some("crash") => ("This page crashed",
+                          "Servo failed while loading or rendering this page."),
+        Some("timeout") => ("The connection timed out",
+                            "The server took too long to respond."),
+        _ => ("Problem loading page", "The page couldn't be loaded."),
+    };
+    let body = format!("<h1>{}</h1>
+<p>{}</p>
+<p><code>{}</code></p>
+<p><a class=\"retry\" href=\"servoshell://retry\">Retry</a></p>",
+        title, description, escape(&failed_url));
+    document(title, &body)
+}
+
+/// servoshell://blocked?url=URL&rule=RULE&count=N
+fn blocked(url: &ServoUrl, _state: &WindowState, _context: &PageContext) -> String {
+    let blocked_url = query_param(url, "url").unwrap_or(String::new());
+    let rule = query_param(url, "rule").unwrap_or(String::new());
+    let count = query_param(url, "count").and_then(|c| u32::from_str(&c).ok()).unwrap_or(1);
+    let body = format!("<h1>Blocked by policy</h1>
+<p>The navigation to <code>{}</code> was blocked by this rule:</p>
+<p><code>{}</code></p>
+<p>{} blocked navigation{} in this tab.</p>",
+        escape(&blocked_url), escape(&rule), count, if count == 1 { "" } else { "s" });
+    document("Blocked by policy", &body)
+}
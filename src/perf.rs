@@ -0,0 +1,149 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Page load timings and frame rate, shown on servoshell://perf. Useful
+//! to tell whether a Servo update made pages slower.
+
+use servo::BrowserId;
+use std::cell::RefCell;
+use std::cmp;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+const MAX_LOADS: usize = 50;
+// In seconds
+const FPS_HISTORY_LENGTH: usize = 60;
+
+#[derive(Clone)]
+pub struct LoadTiming {
+    pub browser: BrowserId,
+    pub url: Option<String>,
+    pub start: Instant,
+    // Since start
+    pub head_parsed: Option<Duration>,
+    pub load_end: Option<Duration>,
+    // First frame presented after the load ended
+    pub first_frame: Option<Duration>,
+}
+
+struct FrameCounter {
+    second_start: Option<Instant>,
+    count: u32,
+    // Frames per second, most recent last
+    history: VecDeque<u32>,
+}
+
+impl FrameCounter {
+    fn push(&mut self, fps: u32) {
+        if self.history.len() == FPS_HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+        self.history.push_back(fps);
+    }
+}
+
+pub struct Perf {
+    // Most recent last
+    loads: RefCell<VecDeque<LoadTiming>>,
+    frames: RefCell<FrameCounter>,
+}
+
+pub fn millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + (duration.subsec_nanos() / 1_000_000) as u64
+}
+
+impl Perf {
+    pub fn new() -> Perf {
+        Perf {
+            loads: RefCell::new(VecDeque::with_capacity(MAX_LOADS)),
+            frames: RefCell::new(FrameCounter {
+                second_start: None,
+                count: 0,
+                history: VecDeque::with_capacity(FPS_HISTORY_LENGTH),
+            }),
+        }
+    }
+
+    pub fn load_start(&self, browser: BrowserId, url: Option<String>) {
+        let mut loads = self.loads.borrow_mut();
+        if loads.len() == MAX_LOADS {
+            loads.pop_front();
+        }
+        loads.push_back(LoadTiming {
+            browser,
+            url,
+            start: Instant::now(),
+            head_parsed: None,
+            load_end: None,
+            first_frame: None,
+        });
+    }
+
+    pub fn head_parsed(&self, browser: BrowserId) {
+        if let Some(load) = self.loads.borrow_mut().iter_mut().rev().find(|l| l.browser == browser) {
+            if load.head_parsed.is_none() {
+                load.head_parsed = Some(load.start.elapsed());
+            }
+        }
+    }
+
+    pub fn load_end(&self, browser: BrowserId, url: Option<String>) {
+        if let Some(load) = self.loads.borrow_mut().iter_mut().rev().find(|l| l.browser == browser) {
+            if load.load_end.is_none() {
+                let duration = load.start.elapsed();
+                load.load_end = Some(duration);
+                // The URL is known once the load committed
+                if url.is_some() {
+                    load.url = url;
+                }
+                info!("Loaded {} in {} ms (head parsed after {} ms)",
+                      load.url.as_ref().map_or("?", |u| u.as_str()),
+                      millis(duration),
+                      load.head_parsed.map_or("?".to_owned(), |d| millis(d).to_string()));
+            }
+        }
+    }
+
+    /// Frames presented since the last call.
+    pub fn frames_presented(&self, frames: &[Instant]) {
+        for &time in frames {
+            for load in self.loads.borrow_mut().iter_mut() {
+                let ended = load.load_end.map(|end| load.start + end);
+                if load.first_frame.is_none() && ended.map_or(false, |ended| time >= ended) {
+                    let duration = time.duration_since(load.start);
+                    load.first_frame = Some(duration);
+                    info!("First frame of {} after {} ms",
+                          load.url.as_ref().map_or("?", |u| u.as_str()), millis(duration));
+                }
+            }
+
+            let mut frames = self.frames.borrow_mut();
+            let second_start = frames.second_start.unwrap_or(time);
+            let elapsed = time.duration_since(second_start).as_secs() as usize;
+            if elapsed > 0 {
+                // Seconds without frames count too
+                let count = frames.count;
+                debug!("{} fps", count);
+                frames.push(count);
+                for _ in 1..cmp::min(elapsed, FPS_HISTORY_LENGTH) {
+                    frames.push(0);
+                }
+                frames.second_start = Some(second_start + Duration::from_secs(elapsed as u64));
+                frames.count = 0;
+            } else {
+                frames.second_start = Some(second_start);
+            }
+            frames.count += 1;
+        }
+    }
+
+    pub fn get_loads(&self) -> Vec<LoadTiming> {
+        self.loads.borrow().iter().cloned().collect()
+    }
+
+    /// Frames per second, for the last complete seconds. Most recent last.
+    pub fn get_fps_history(&self) -> Vec<u32> {
+        self.frames.borrow().history.iter().cloned().collect()
+    }
+}
@@ -9,6 +9,7 @@ use objc::declare::ClassDecl;
 use objc::runtime::{Class, Object, Sel};
 use servo::ServoCursor;
 use state::AppState;
+use std::cell::Cell;
 use std::env;
 use std::os::raw::c_void;
 use std::path::PathBuf;
@@ -74,7 +75,8 @@ fn register() {
 
 
 pub struct App {
-    nsapp: id
+    nsapp: id,
+    is_running: Cell<bool>,
 }
 
 impl App {
@@ -247,7 +249,7 @@ impl AppMethods for App {
             msg_send![nsapp, setDelegate:delegate];
         }
 
-        let app = App {nsapp: nsapp};
+        let app = App {nsapp: nsapp, is_running: Cell::new(true)};
 
         Ok(app)
     }
@@ -274,7 +276,7 @@ impl AppMethods for App {
 
         unsafe { msg_send![self.nsapp, finishLaunching] };
 
-        loop {
+        while self.is_running.get() {
             unsafe {
                 let pool = NSAutoreleasePool::new(nil);
 
@@ -312,12 +314,26 @@ impl AppMethods for App {
         }
     }
 
-    fn new_window<'a>(&self) -> Result<Box<WindowMethods>, &'a str> {
+    fn quit(&self) {
+        self.is_running.set(false);
+    }
+
+    fn new_window<'a>(&self, size: (u32, u32), fullscreen: bool) -> Result<Box<WindowMethods>, &'a str> {
         let (nswindow, nspopover) = match App::create_native_window() {
             Ok(w) => w,
             Err(msg) => return Err(&msg),
         };
 
+        let (width, height) = size;
+        unsafe {
+            msg_send![nswindow, setContentSize:NSSize::new(width as f64, height as f64)];
+            if fullscreen {
+                let toolbar: id = msg_send![nswindow, toolbar];
+                msg_send![toolbar, setVisible:NO];
+                msg_send![nswindow, toggleFullScreen:nil];
+            }
+        }
+
         Ok(Box::new(window::Window::new(nswindow, nspopover)))
     }
 
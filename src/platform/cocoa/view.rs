@@ -15,6 +15,7 @@ use self::cgl::{CGLEnable, kCGLCECrashOnRemovedFunctions};
 use self::core_foundation::base::TCFType;
 use self::core_foundation::bundle::{CFBundleGetBundleWithIdentifier, CFBundleGetFunctionPointerForName};
 use self::core_foundation::string::CFString;
+use shortcuts;
 use std::os::raw::c_void;
 use std::rc::Rc;
 use std::{ffi, str};
@@ -70,11 +71,16 @@ pub fn register() {
                     let phase = match nsevent.phase() {
                         appkit::NSEventPhaseMayBegin | appkit::NSEventPhaseBegan => TouchPhase::Started,
                         appkit::NSEventPhaseEnded => TouchPhase::Ended,
+                        appkit::NSEventPhaseCancelled => TouchPhase::Cancelled,
                         _ => TouchPhase::Moved,
                     };
                     let event = ViewEvent::MouseWheel(delta, phase);
                     utils::get_event_queue(this).push(event);
                 },
+                NSEventTypeMagnify => {
+                    let magnification = 1.0 + nsevent.magnification() as f32;
+                    utils::get_event_queue(this).push(ViewEvent::Pinch(magnification));
+                }
                 NSMouseMoved => {
                     let (x, y) = cursor_coordinates_in_view(this, nsevent);
                     let event = ViewEvent::MouseMoved(x, y);
@@ -124,6 +130,29 @@ pub fn register() {
         YES
     }
 
+    // The menu key equivalents would run before the page sees the key.
+    // Shortcuts go to the page first instead, see shortcuts.rs. The
+    // reserved ones, and all of them while the urlbar has the focus, are
+    // left to the menu.
+    extern fn perform_key_equivalent(this: &Object, _sel: Sel, nsevent: id) -> BOOL {
+        unsafe {
+            let window: id = msg_send![this, window];
+            let first_responder: id = msg_send![window, firstResponder];
+            if first_responder != this as *const Object as id {
+                return NO;
+            }
+            let is_shortcut = to_virtual_key_code(NSEvent::keyCode(nsevent)).map_or(false, |key| {
+                shortcuts::command(key, to_mods(nsevent)).is_some()
+            });
+            if is_shortcut {
+                store_nsevent(this, sel!(keyDown:), nsevent);
+                YES
+            } else {
+                NO
+            }
+        }
+    }
+
     extern fn set_frame_size(this: &Object, _sel: Sel, size: NSSize) {
         unsafe {
             msg_send![super(this, Class::get("NSView").unwrap()), setFrameSize:size];
@@ -139,6 +168,7 @@ pub fn register() {
 
     unsafe {
         class.add_method(sel!(scrollWheel:), store_nsevent as extern fn(&Object, Sel, id));
+        class.add_method(sel!(magnifyWithEvent:), store_nsevent as extern fn(&Object, Sel, id));
         class.add_method(sel!(mouseDown:), store_nsevent as extern fn(&Object, Sel, id));
         class.add_method(sel!(mouseUp:), store_nsevent as extern fn(&Object, Sel, id));
         class.add_method(sel!(mouseMoved:), store_nsevent as extern fn(&Object, Sel, id));
@@ -146,6 +176,7 @@ pub fn register() {
         class.add_method(sel!(keyUp:), store_nsevent as extern fn(&Object, Sel, id));
 
         class.add_method(sel!(acceptsFirstResponder), accept_first_responder as extern fn(&Object, Sel) -> BOOL);
+        class.add_method(sel!(performKeyEquivalent:), perform_key_equivalent as extern fn(&Object, Sel, id) -> BOOL);
 
         class.add_method(sel!(setFrameSize:), set_frame_size as extern fn(&Object, Sel, NSSize));
 
@@ -11,7 +11,7 @@ use objc::declare::ClassDecl;
 use objc::runtime::{Class, Object, Sel};
 use platform::View;
 use servo::EventLoopWaker;
-use state::WindowState;
+use state::{Focus, WindowState};
 use std::f64;
 use std::ffi::CStr;
 use std::os::raw::c_void;
@@ -91,6 +91,10 @@ pub fn register() {
                     Some(WindowEvent::OptionsClosed)
                 } else if NSString::isEqualToString(name, "NSControlTextDidEndEditingNotification") {
                     Some(WindowEvent::UrlbarFocusChanged(false))
+                } else if NSString::isEqualToString(name, "NSWindowDidBecomeKeyNotification") {
+                    Some(WindowEvent::WindowFocusChanged(true))
+                } else if NSString::isEqualToString(name, "NSWindowDidResignKeyNotification") {
+                    Some(WindowEvent::WindowFocusChanged(false))
                 } else {
                     None
                 }
@@ -128,6 +132,7 @@ pub fn register() {
             else if action == sel!(shellOpenLocation:) { WindowCommand::OpenLocation }
             else if action == sel!(shellNewTab:) { WindowCommand::NewTab }
             else if action == sel!(shellCloseTab:) { WindowCommand::CloseTab}
+            else if action == sel!(shellReopenClosedTab:) { WindowCommand::ReopenClosedTab }
             else if action == sel!(shellNextTab:) { WindowCommand::NextTab}
             else if action == sel!(shellPrevTab:) { WindowCommand::PrevTab}
             else if action == sel!(shellZoomIn:) { WindowCommand::ZoomIn }
@@ -136,6 +141,8 @@ pub fn register() {
             else if action == sel!(shellNavigateBack:) { WindowCommand::NavigateBack }
             else if action == sel!(shellNavigateForward:) { WindowCommand::NavigateForward }
             else if action == sel!(shellOpenInDefaultBrowser:) { WindowCommand::OpenInDefaultBrowser }
+            else if action == sel!(shellCopyUrl:) { WindowCommand::CopyUrl }
+            else if action == sel!(shellPasteAndGo:) { WindowCommand::PasteAndGo }
             else if action == sel!(shellToggleSidebar:) { WindowCommand::ToggleSidebar }
             else if action == sel!(shellShowOptions:) { WindowCommand::ShowOptions }
             else if action == sel!(shellToggleOptionShowLogs:) { WindowCommand::ToggleOptionShowLogs }
@@ -173,6 +180,8 @@ pub fn register() {
                 true
             } else if action == sel!(shellCloseTab:) {
                 true
+            } else if action == sel!(shellReopenClosedTab:) {
+                !get_win_state().closed_tabs.is_empty()
             } else if action == sel!(shellNextTab:) {
                 true
             } else if action == sel!(shellPrevTab:) {
@@ -192,6 +201,10 @@ pub fn register() {
                     Some(ref url) if url != "about:blank" => true,
                     _ => false
                 }
+            } else if action == sel!(shellCopyUrl:) {
+                state.url.is_some()
+            } else if action == sel!(shellPasteAndGo:) {
+                true
             } else if action == sel!(shellToggleSidebar:) {
                 true
             } else if action == sel!(shellShowOptions:) {
@@ -261,6 +274,8 @@ pub fn register() {
             class.add_method(sel!(windowDidEnterFullScreen:), record_notification as extern fn(&Object, Sel, id));
             class.add_method(sel!(windowDidExitFullScreen:), record_notification as extern fn(&Object, Sel, id));
             class.add_method(sel!(windowWillClose:), record_notification as extern fn(&Object, Sel, id));
+            class.add_method(sel!(windowDidBecomeKey:), record_notification as extern fn(&Object, Sel, id));
+            class.add_method(sel!(windowDidResignKey:), record_notification as extern fn(&Object, Sel, id));
             class.add_method(sel!(popoverWillClose:), record_notification as extern fn(&Object, Sel, id));
             class.add_method(sel!(controlTextDidEndEditing:), record_notification as extern fn(&Object, Sel, id));
 
@@ -269,6 +284,7 @@ pub fn register() {
             class.add_method(sel!(shellOpenLocation:), record_command as extern fn(&Object, Sel, id));
             class.add_method(sel!(shellNewTab:), record_command as extern fn(&Object, Sel, id));
             class.add_method(sel!(shellCloseTab:), record_command as extern fn(&Object, Sel, id));
+            class.add_method(sel!(shellReopenClosedTab:), record_command as extern fn(&Object, Sel, id));
             class.add_method(sel!(shellNextTab:), record_command as extern fn(&Object, Sel, id));
             class.add_method(sel!(shellPrevTab:), record_command as extern fn(&Object, Sel, id));
             class.add_method(sel!(shellZoomIn:), record_command as extern fn(&Object, Sel, id));
@@ -277,6 +293,8 @@ pub fn register() {
             class.add_method(sel!(shellNavigateBack:), record_command as extern fn(&Object, Sel, id));
             class.add_method(sel!(shellNavigateForward:), record_command as extern fn(&Object, Sel, id));
             class.add_method(sel!(shellOpenInDefaultBrowser:), record_command as extern fn(&Object, Sel, id));
+            class.add_method(sel!(shellCopyUrl:), record_command as extern fn(&Object, Sel, id));
+            class.add_method(sel!(shellPasteAndGo:), record_command as extern fn(&Object, Sel, id));
             class.add_method(sel!(shellToggleSidebar:), record_command as extern fn(&Object, Sel, id));
             class.add_method(sel!(shellShowOptions:), record_command as extern fn(&Object, Sel, id));
             class.add_method(sel!(shellToggleOptionShowLogs:), record_command as extern fn(&Object, Sel, id));
@@ -557,12 +575,12 @@ impl WindowMethods for Window {
         unsafe {
             let view = msg_send![item, view];
             let field = utils::get_view_by_id(view, "shellToolbarViewUrlbarTextfield").unwrap();
-            match state.browsers[idx].url {
-                Some(ref url) if url != "about:blank" => msg_send![field, setStringValue:NSString::alloc(nil).init_str(url)],
+            match state.browsers[idx].display_url() {
+                Some(url) if url != "about:blank" => msg_send![field, setStringValue:NSString::alloc(nil).init_str(url)],
                 _ => msg_send![field, setStringValue:NSString::alloc(nil).init_str("")],
             };
 
-            if state.urlbar_focused {
+            if state.focus == Focus::Urlbar {
                 msg_send![field, becomeFirstResponder];
             }
         }
@@ -617,11 +635,20 @@ impl WindowMethods for Window {
             for i in 0..state_count {
                 // FIXME: alloc…
                 let item: id = msg_send![tabview, tabViewItemAtIndex:i];
-                let nsstring = match state.browsers[i].title {
-                    Some(ref title) => NSString::alloc(nil).init_str(title),
-                    None => NSString::alloc(nil).init_str("No Title"),
-                };
-                msg_send![item, setLabel:nsstring];
+                let title = state.browsers[i].title.as_ref().map_or("No Title", |t| t.as_str());
+                msg_send![item, setLabel:NSString::alloc(nil).init_str(title)];
+            }
+        }
+
+        // The title is only shown in private mode, to mark the window
+        unsafe {
+            let title = state.browsers[idx].title.as_ref().map_or("", |t| t.as_str());
+            if state.private {
+                let title = if title.is_empty() { "Private".to_owned() } else { format!("{} (Private)", title) };
+                self.nswindow.setTitle_(NSString::alloc(nil).init_str(&title));
+                self.nswindow.setTitleVisibility_(NSWindowTitleVisibility::NSWindowTitleVisible);
+            } else {
+                self.nswindow.setTitle_(NSString::alloc(nil).init_str(title));
             }
         }
 
@@ -637,8 +664,8 @@ impl WindowMethods for Window {
 
         // FIXME: diff
         let textfield = utils::get_view_by_id(self.nswindow, "shellStatusLabel").unwrap();
-        match state.status {
-            Some(ref status) => {
+        match state.zoom_indicator.as_ref().or(state.status.as_ref()) {
+            Some(status) => {
                 unsafe {
                     msg_send![textfield, setHidden:NO];
                     let string = NSString::alloc(nil).init_str(status);
@@ -687,6 +714,31 @@ impl WindowMethods for Window {
         }
     }
 
+    fn supports_clipboard(&self) -> bool {
+        true
+    }
+
+    fn get_clipboard_contents(&self) -> Option<String> {
+        unsafe {
+            let pasteboard = NSPasteboard::generalPasteboard(nil);
+            let text = pasteboard.stringForType(NSPasteboardTypeString);
+            if text == nil {
+                return None;
+            }
+            let text: *const libc::c_char = msg_send![text, UTF8String];
+            Some(CStr::from_ptr(text).to_string_lossy().into_owned())
+        }
+    }
+
+    fn set_clipboard_contents(&self, contents: &str) {
+        unsafe {
+            let pasteboard = NSPasteboard::generalPasteboard(nil);
+            pasteboard.clearContents();
+            let contents = NSString::alloc(nil).init_str(contents);
+            pasteboard.setString_forType(contents, NSPasteboardTypeString);
+        }
+    }
+
     fn get_events(&self) -> Vec<WindowEvent> {
         let nsobject = unsafe { &*self.nswindow};
         utils::get_event_queue(nsobject).drain(..).collect()
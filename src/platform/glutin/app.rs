@@ -36,6 +36,7 @@ impl EventLoopWaker for WinitEventLoopWaker {
 pub struct App {
     event_loop: RefCell<glutin::EventsLoop>,
     event_loop_waker: Box<EventLoopWaker>,
+    is_running: Cell<bool>,
     windows: Rc<RefCell<HashMap<glutin::WindowId, GlutinWindow>>>,
 }
 
@@ -79,6 +80,7 @@ impl AppMethods for App {
             windows,
             event_loop: RefCell::new(event_loop),
             event_loop_waker,
+            is_running: Cell::new(true),
         })
     }
 
@@ -119,16 +121,21 @@ impl AppMethods for App {
         vec![]
     }
 
-    fn new_window<'a>(&self) -> Result<Box<WindowMethods>, &'a str> {
+    fn new_window<'a>(&self, size: (u32, u32), fullscreen: bool) -> Result<Box<WindowMethods>, &'a str> {
 
         #[cfg(target_os = "windows")]
         let factor = utils::windows_hidpi_factor();
         #[cfg(not(target_os = "windows"))]
         let factor = 1.0;
 
-        let window = glutin::WindowBuilder::new()
-            .with_dimensions(1024 * factor as u32,
-                             768 * factor as u32);
+        let (width, height) = size;
+        let mut window = glutin::WindowBuilder::new()
+            .with_dimensions((width as f32 * factor).round() as u32,
+                             (height as f32 * factor).round() as u32);
+        if fullscreen {
+            window = window.with_fullscreen(glutin::get_primary_monitor())
+                           .with_decorations(false);
+        }
         let context = glutin::ContextBuilder::new()
             .with_gl(glutin::GlRequest::Specific(glutin::Api::OpenGl, (3, 2)))
             .with_vsync(true);
@@ -147,6 +154,11 @@ impl AppMethods for App {
 
         let id = glutin_window.id();
 
+        #[cfg(target_os = "windows")]
+        let hidpi_factor = factor;
+        #[cfg(not(target_os = "windows"))]
+        let hidpi_factor = glutin_window.hidpi_factor();
+
         self.windows.borrow_mut().insert(id, GlutinWindow {
             gl,
             glutin_window,
@@ -156,6 +168,8 @@ impl AppMethods for App {
             view_events: vec![],
             window_events: vec![],
             mouse_coordinate: (0, 0),
+            context_menu: None,
+            hidpi_factor,
         });
 
         Ok(Box::new(Window::new(id, self.windows.clone())))
@@ -178,13 +192,18 @@ impl AppMethods for App {
                                     call_callback = true;
                                 }
                                 None => {
-                                    match (*window).glutin_event_to_view_event(&event) {
-                                        Some(event) => {
-                                            window.view_events.push(event);
-                                            call_callback = true;
-                                        }
-                                        None => {
-                                            warn!("Got unknown glutin event: {:?}", event);
+                                    if let Some(event) = (*window).glutin_event_to_window_event(&event) {
+                                        window.window_events.push(event);
+                                        call_callback = true;
+                                    } else {
+                                        match (*window).glutin_event_to_view_event(&event) {
+                                            Some(event) => {
+                                                window.view_events.push(event);
+                                                call_callback = true;
+                                            }
+                                            None => {
+                                                warn!("Got unknown glutin event: {:?}", event);
+                                            }
                                         }
                                     }
                                 }
@@ -207,8 +226,16 @@ impl AppMethods for App {
             if call_callback {
                 callback();
             }
-            glutin::ControlFlow::Continue
+            if self.is_running.get() {
+                glutin::ControlFlow::Continue
+            } else {
+                glutin::ControlFlow::Break
+            }
         });
         callback()
     }
+
+    fn quit(&self) {
+        self.is_running.set(false);
+    }
 }
@@ -25,12 +25,30 @@ pub struct GlutinWindow {
     key_modifiers: Cell<KeyModifiers>,
     last_pressed_key: Cell<Option<Key>>,
     mouse_coordinate: (i32, i32),
+    // Last known scale factor. Moving the window to another monitor can
+    // change it.
+    hidpi_factor: f32,
+    // Commands of the context menu shown in the title bar, picked with 1-9
+    context_menu: Option<Vec<WindowCommand>>,
     view_events: Vec<ViewEvent>,
     window_events: Vec<WindowEvent>,
 }
 
 impl GlutinWindow {
 
+    #[cfg(not(target_os = "windows"))]
+    pub fn hidpi_factor(&self) -> f32 {
+        self.glutin_window.hidpi_factor()
+    }
+
+    // The system-wide factor: the process isn't per-monitor DPI aware, so
+    // moving the window to a monitor with another scale factor isn't
+    // detected on Windows.
+    #[cfg(target_os = "windows")]
+    pub fn hidpi_factor(&self) -> f32 {
+        utils::windows_hidpi_factor()
+    }
+
     pub fn glutin_event_to_command(&self, event: &glutin::WindowEvent) -> Option<WindowCommand> {
         match *event {
             glutin::WindowEvent::KeyboardInput{ input: glutin::KeyboardInput {
@@ -39,27 +57,17 @@ impl GlutinWindow {
                 modifiers,
                 ..
             }, ..} => {
-                match (virtual_keycode, utils::cmd_or_ctrl(modifiers), modifiers.ctrl, modifiers.shift) {
-                    (Some(glutin::VirtualKeyCode::R), true, _, _) => Some(WindowCommand::Reload),
-                    (Some(glutin::VirtualKeyCode::Left), true, _, _) => Some(WindowCommand::NavigateBack),
-                    (Some(glutin::VirtualKeyCode::Right), true, _, _) => Some(WindowCommand::NavigateForward),
-                    (Some(glutin::VirtualKeyCode::L), true, _, _) => Some(WindowCommand::OpenLocation),
-                    (Some(glutin::VirtualKeyCode::Equals), true, _, _) => Some(WindowCommand::ZoomIn),
-                    (Some(glutin::VirtualKeyCode::Minus), true, _, _) => Some(WindowCommand::ZoomOut),
-                    (Some(glutin::VirtualKeyCode::Key0), true, _, _) => Some(WindowCommand::ZoomToActualSize),
-                    (Some(glutin::VirtualKeyCode::T), true, _, _) => Some(WindowCommand::NewTab),
-                    (Some(glutin::VirtualKeyCode::W), true, _, _) => Some(WindowCommand::CloseTab),
-                    (Some(glutin::VirtualKeyCode::Tab), _, true, false) => Some(WindowCommand::NextTab),
-                    (Some(glutin::VirtualKeyCode::Tab), _, true, true) => Some(WindowCommand::PrevTab),
-                    (Some(glutin::VirtualKeyCode::Key1), true, _, _) => Some(WindowCommand::SelectTab(0)),
-                    (Some(glutin::VirtualKeyCode::Key2), true, _, _) => Some(WindowCommand::SelectTab(1)),
-                    (Some(glutin::VirtualKeyCode::Key3), true, _, _) => Some(WindowCommand::SelectTab(2)),
-                    (Some(glutin::VirtualKeyCode::Key4), true, _, _) => Some(WindowCommand::SelectTab(3)),
-                    (Some(glutin::VirtualKeyCode::Key5), true, _, _) => Some(WindowCommand::SelectTab(4)),
-                    (Some(glutin::VirtualKeyCode::Key6), true, _, _) => Some(WindowCommand::SelectTab(5)),
-                    (Some(glutin::VirtualKeyCode::Key7), true, _, _) => Some(WindowCommand::SelectTab(6)),
-                    (Some(glutin::VirtualKeyCode::Key8), true, _, _) => Some(WindowCommand::SelectTab(7)),
-                    (Some(glutin::VirtualKeyCode::Key9), true, _, _) => Some(WindowCommand::SelectTab(8)),
+                if let Some(ref items) = self.context_menu {
+                    if !utils::cmd_or_ctrl(modifiers) {
+                        let index = virtual_keycode.and_then(utils::digit_key_to_index);
+                        if let Some(cmd) = index.and_then(|index| items.get(index)) {
+                            return Some(cmd.clone());
+                        }
+                    }
+                }
+                // Reserved shortcuts. The others go to the page first, see shortcuts.rs
+                match (virtual_keycode, utils::cmd_or_ctrl(modifiers)) {
+                    (Some(glutin::VirtualKeyCode::W), true) => Some(WindowCommand::CloseTab),
                     _ => None
                 }
             }
@@ -67,18 +75,35 @@ impl GlutinWindow {
         }
     }
 
+    pub fn glutin_event_to_window_event(&self, event: &glutin::WindowEvent) -> Option<WindowEvent> {
+        match *event {
+            glutin::WindowEvent::Focused(focused) => Some(WindowEvent::WindowFocusChanged(focused)),
+            _ => None
+        }
+    }
+
     pub fn glutin_event_to_view_event(&mut self, event: &glutin::WindowEvent) -> Option<ViewEvent> {
         match *event {
             glutin::WindowEvent::Resized(..) => {
+                self.hidpi_factor = self.hidpi_factor();
                 Some(ViewEvent::GeometryDidChange)
             }
+            glutin::WindowEvent::Moved(..) => {
+                let factor = self.hidpi_factor();
+                if factor != self.hidpi_factor {
+                    info!("Scale factor changed: {} -> {}", self.hidpi_factor, factor);
+                    self.hidpi_factor = factor;
+                    Some(ViewEvent::GeometryDidChange)
+                } else {
+                    None
+                }
+            }
             glutin::WindowEvent::MouseMoved{position: (x, y), ..} => {
                 self.mouse_coordinate = (x as i32, y as i32);
                 Some(ViewEvent::MouseMoved(x as i32, y as i32))
             }
             glutin::WindowEvent::MouseWheel{delta, phase, ..} => {
                 let delta = match delta {
-                    // FIXME: magic value
                     glutin::MouseScrollDelta::LineDelta(dx, dy) => MouseScrollDelta::LineDelta(dx, dy),
                     glutin::MouseScrollDelta::PixelDelta(dx, dy) => MouseScrollDelta::PixelDelta(dx, dy),
                 };
@@ -86,17 +111,32 @@ impl GlutinWindow {
                     glutin::TouchPhase::Started => TouchPhase::Started,
                     glutin::TouchPhase::Moved => TouchPhase::Moved,
                     glutin::TouchPhase::Ended => TouchPhase::Ended,
-                    // FIXME:
-                    glutin::TouchPhase::Cancelled => TouchPhase::Ended,
+                    glutin::TouchPhase::Cancelled => TouchPhase::Cancelled,
                 };
                 Some(ViewEvent::MouseWheel(delta, phase))
             }
-            glutin::WindowEvent::MouseInput{state, button: glutin::MouseButton::Left, ..} => {
+            glutin::WindowEvent::Touch(glutin::Touch {phase, location: (x, y), id, ..}) => {
+                let phase = match phase {
+                    glutin::TouchPhase::Started => TouchPhase::Started,
+                    glutin::TouchPhase::Moved => TouchPhase::Moved,
+                    glutin::TouchPhase::Ended => TouchPhase::Ended,
+                    glutin::TouchPhase::Cancelled => TouchPhase::Cancelled,
+                };
+                Some(ViewEvent::Touch(id, phase, x as i32, y as i32))
+            }
+            // FIXME: no pinch events in winit yet
+            glutin::WindowEvent::MouseInput{state, button, ..} => {
                 let state = match state {
                     glutin::ElementState::Released => ElementState::Released,
                     glutin::ElementState::Pressed => ElementState::Pressed,
                 };
-                Some(ViewEvent::MouseInput(state, MouseButton::Left, self.mouse_coordinate.0, self.mouse_coordinate.1))
+                let button = match button {
+                    glutin::MouseButton::Left => MouseButton::Left,
+                    glutin::MouseButton::Right => MouseButton::Right,
+                    glutin::MouseButton::Middle => MouseButton::Middle,
+                    glutin::MouseButton::Other(_) => return None,
+                };
+                Some(ViewEvent::MouseInput(state, button, self.mouse_coordinate.0, self.mouse_coordinate.1))
             }
             glutin::WindowEvent::ReceivedCharacter(ch) => {
 
@@ -227,6 +227,22 @@ pub fn servo_cursor_to_glutin_cursor(servo_cursor: ServoCursor) -> glutin::Mouse
     }
 }
 
+// 1-9 keys to 0-8
+pub fn digit_key_to_index(key: glutin::VirtualKeyCode) -> Option<usize> {
+    match key {
+        glutin::VirtualKeyCode::Key1 => Some(0),
+        glutin::VirtualKeyCode::Key2 => Some(1),
+        glutin::VirtualKeyCode::Key3 => Some(2),
+        glutin::VirtualKeyCode::Key4 => Some(3),
+        glutin::VirtualKeyCode::Key5 => Some(4),
+        glutin::VirtualKeyCode::Key6 => Some(5),
+        glutin::VirtualKeyCode::Key7 => Some(6),
+        glutin::VirtualKeyCode::Key8 => Some(7),
+        glutin::VirtualKeyCode::Key9 => Some(8),
+        _ => None,
+    }
+}
+
 // Some shortcuts use Cmd on Mac and Control on other systems.
 pub fn cmd_or_ctrl(m: glutin::ModifiersState) -> bool {
     if cfg!(target_os = "macos") {
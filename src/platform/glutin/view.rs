@@ -19,16 +19,10 @@ impl View {
         View { id, windows }
     }
 
-    #[cfg(not(target_os = "windows"))]
     fn hidpi_factor(&self) -> f32 {
         let windows = self.windows.borrow();
         let win = windows.get(&self.id).unwrap();
-        win.glutin_window.hidpi_factor()
-    }
-
-    #[cfg(target_os = "windows")]
-    fn hidpi_factor(&self) -> f32 {
-        super::utils::windows_hidpi_factor()
+        win.hidpi_factor()
     }
 }
 
@@ -43,8 +37,8 @@ impl ViewMethods for View {
         #[cfg(not(target_os = "windows"))]
         let factor = 1.0;
 
-        width /= factor as u32;
-        height /= factor as u32;
+        width = (width as f32 / factor).round() as u32;
+        height = (height as f32 / factor).round() as u32;
 
         DrawableGeometry {
             view_size: (width, height),
@@ -2,11 +2,12 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use clipboard::{ClipboardContext, ClipboardProvider};
 use glutin;
 use logs::ShellLog;
 use platform::View;
 use servo::EventLoopWaker;
-use state::WindowState;
+use state::{Focus, WindowState};
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
@@ -18,11 +19,20 @@ use traits::window::{WindowCommand, WindowEvent, WindowMethods};
 pub struct Window {
     id: glutin::WindowId,
     windows: Rc<RefCell<HashMap<glutin::WindowId, GlutinWindow>>>,
+    // X11 CLIPBOARD selection. None if the X server can't be reached.
+    clipboard: RefCell<Option<ClipboardContext>>,
 }
 
 impl Window {
     pub fn new(id: glutin::WindowId, windows: Rc<RefCell<HashMap<glutin::WindowId, GlutinWindow>>>) -> Window {
-        Window { id, windows }
+        let clipboard = match ClipboardContext::new() {
+            Ok(clipboard) => Some(clipboard),
+            Err(err) => {
+                warn!("Clipboard not available: {}", err);
+                None
+            }
+        };
+        Window { id, windows, clipboard: RefCell::new(clipboard) }
     }
 }
 
@@ -38,12 +48,37 @@ impl WindowMethods for Window {
             format!("{} {} {:15.15} {}|", f, selected, title, loading)
         });
 
+        let text = match state.zoom_indicator {
+            Some(ref zoom) => format!("{} {}", text, zoom),
+            None => text,
+        };
+
+        // The context menu replaces the tabs
+        let text = match state.context_menu {
+            Some(ref items) => items.iter().take(9).enumerate().fold("Menu:".to_owned(), |f, (idx, item)| {
+                format!("{} [{}] {}", f, idx + 1, item.label)
+            }),
+            None => text,
+        };
+
+        let text = if state.private {
+            format!("[Private] {}", text)
+        } else {
+            text
+        };
+
         let mut windows = self.windows.borrow_mut();
-        windows.get_mut(&self.id).unwrap().glutin_window.set_title(&text);
+        {
+            let win = windows.get_mut(&self.id).unwrap();
+            win.glutin_window.set_title(&text);
+            win.context_menu = state.context_menu.as_ref().map(|items| {
+                items.iter().map(|item| item.command.clone()).collect()
+            });
+        }
 
-        if state.urlbar_focused {
+        if state.focus == Focus::Urlbar {
             let url = format!("{}", state.browsers[state.current_browser_index.unwrap()]
-                              .url.as_ref().map_or("", |t| t.as_str()));
+                              .display_url().map_or("", |t| t.as_str()));
             match tinyfiledialogs::input_box("Search or type URL", "Search or type URL", &url) {
                 Some(input) => {
                     let win = windows.get_mut(&self.id).unwrap();
@@ -73,6 +108,24 @@ impl WindowMethods for Window {
 
     fn append_logs(&self, _logs: &Vec<ShellLog>) {
     }
+
+    fn supports_clipboard(&self) -> bool {
+        self.clipboard.borrow().is_some()
+    }
+
+    fn get_clipboard_contents(&self) -> Option<String> {
+        self.clipboard.borrow_mut().as_mut().and_then(|clipboard| {
+            clipboard.get_contents().map_err(|err| warn!("Can't read the clipboard: {}", err)).ok()
+        })
+    }
+
+    fn set_clipboard_contents(&self, contents: &str) {
+        if let Some(ref mut clipboard) = *self.clipboard.borrow_mut() {
+            if let Err(err) = clipboard.set_contents(contents.to_owned()) {
+                warn!("Can't write to the clipboard: {}", err);
+            }
+        }
+    }
 }
 
 
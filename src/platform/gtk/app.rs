@@ -4,14 +4,16 @@
 
 use std::cell::{Cell, RefCell};
 use std::env;
+use std::mem;
 use std::path::PathBuf;
 use std::ptr;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use epoxy;
 use gdk;
-use gdk::{POINTER_MOTION_MASK, SCROLL_MASK};
+use gdk::{POINTER_MOTION_MASK, SCROLL_MASK, TOUCH_MASK};
 use glib_itc::{Receiver, Sender, channel};
 use gtk;
 use gtk::{
@@ -20,13 +22,23 @@ use gtk::{
     ContainerExt,
     Entry,
     EntryExt,
+    EventControllerExt,
+    GestureExt,
+    GestureLongPress,
+    GestureZoom,
     GLArea,
     GLAreaExt,
     Image,
     Inhibit,
+    Menu,
+    MenuExtManual,
+    MenuItem,
+    MenuItemExt,
+    MenuShellExt,
     Notebook,
     NotebookExt,
     PackType,
+    PropagationPhase,
     SeparatorToolItem,
     Toolbar,
     ToolButton,
@@ -43,29 +55,38 @@ use shared_library::dynamic_library::DynamicLibrary;
 use state::AppState;
 use super::utils;
 use traits::app::{AppEvent, AppMethods};
-use traits::view::{gl, ElementState, KeyModifiers, MouseButton, MouseScrollDelta, TouchPhase, ViewEvent};
+use traits::view::{gl, ElementState, KeyModifiers, MouseScrollDelta, TouchPhase, ViewEvent};
 use traits::window::{WindowCommand, WindowEvent, WindowMethods};
 
 // TODO: remove.
 const WINDOW_ID: usize = 0;
 
+// Touchpad pinches. gdk only exposes it with GTK 3.18.
+const GDK_TOUCHPAD_GESTURE_MASK: i32 = 1 << 24;
+
 pub struct GtkEventLoopWaker {
     tx: Arc<Mutex<Sender>>,
+    // The receiver only wakes up Servo. This tells the main loop to
+    // also run the shell callback.
+    awaken: Arc<AtomicBool>,
 }
 
 impl EventLoopWaker for GtkEventLoopWaker {
     fn clone(&self) -> Box<EventLoopWaker + Send> {
         Box::new(GtkEventLoopWaker {
             tx: self.tx.clone(),
+            awaken: self.awaken.clone(),
         })
     }
 
     fn wake(&self) {
+        self.awaken.store(true, Ordering::SeqCst);
         self.tx.lock().unwrap().send();
     }
 }
 
 pub struct App {
+    awaken: Arc<AtomicBool>,
     call_callback: Rc<Cell<bool>>,
     event_loop_waker: Box<EventLoopWaker>,
     is_running: Rc<Cell<bool>>,
@@ -108,11 +129,14 @@ impl App {
 impl AppMethods for App {
     fn new<'a>() -> Result<App, &'a str> {
         let (tx, rx) = channel();
+        let awaken = Arc::new(AtomicBool::new(false));
         let event_loop_waker = Box::new(GtkEventLoopWaker {
             tx: Arc::new(Mutex::new(tx)),
+            awaken: awaken.clone(),
         });
         let windows = Rc::new(RefCell::new(vec![]));
         Ok(App {
+            awaken,
             call_callback: Rc::new(Cell::new(false)),
             event_loop_waker,
             is_running: Rc::new(Cell::new(true)),
@@ -160,7 +184,7 @@ impl AppMethods for App {
         vec![]
     }
 
-    fn new_window<'a>(&self) -> Result<Box<WindowMethods>, &'a str> {
+    fn new_window<'a>(&self, size: (u32, u32), fullscreen: bool) -> Result<Box<WindowMethods>, &'a str> {
 
         #[cfg(target_os = "windows")]
         let factor = utils::windows_hidpi_factor();
@@ -168,7 +192,10 @@ impl AppMethods for App {
         let factor = 1.0f32;
 
         let gtk_window = gtk::Window::new(WindowType::Toplevel);
-        gtk_window.set_size_request(1024 * factor as i32, 768 * factor as i32);
+        let (width, height) = size;
+        gtk_window.set_size_request((width as f32 * factor) as i32, (height as f32 * factor) as i32);
+
+        gtk_window.add_events(TOUCH_MASK.bits() as i32);
 
         let windows = self.windows.clone();
         let call_callback = self.call_callback.clone();
@@ -193,9 +220,13 @@ impl AppMethods for App {
         let previous_button = ToolButton::new(&icon("go-previous"), None);
         toolbar.add(&previous_button);
 
+        let long_pressed = add_history_menu(&previous_button, true, self.windows.clone(), self.call_callback.clone());
         let call_callback = self.call_callback.clone();
         let windows = self.windows.clone();
         previous_button.connect_clicked(move |_| {
+            if long_pressed.replace(false) {
+                return;
+            }
             let mut windows = windows.borrow_mut();
             let win: &mut GtkWindow = windows.get_mut(WINDOW_ID).unwrap();
             win.window_events.push(WindowEvent::DoCommand(WindowCommand::NavigateBack));
@@ -205,9 +236,13 @@ impl AppMethods for App {
         let next_button = ToolButton::new(&icon("go-next"), None);
         toolbar.add(&next_button);
 
+        let long_pressed = add_history_menu(&next_button, false, self.windows.clone(), self.call_callback.clone());
         let call_callback = self.call_callback.clone();
         let windows = self.windows.clone();
         next_button.connect_clicked(move |_| {
+            if long_pressed.replace(false) {
+                return;
+            }
             let mut windows = windows.borrow_mut();
             let win: &mut GtkWindow = windows.get_mut(WINDOW_ID).unwrap();
             win.window_events.push(WindowEvent::DoCommand(WindowCommand::NavigateForward));
@@ -264,10 +299,28 @@ impl AppMethods for App {
         let gl_area = GLArea::new();
         gl_area.set_auto_render(false);
         gl_area.set_has_depth_buffer(true);
-        gl_area.add_events((POINTER_MOTION_MASK | SCROLL_MASK).bits() as i32);
+        gl_area.add_events((POINTER_MOTION_MASK | SCROLL_MASK | TOUCH_MASK).bits() as i32 |
+                           GDK_TOUCHPAD_GESTURE_MASK);
         gl_area.set_vexpand(true);
         tabs.add(&gl_area);
 
+        let call_callback = self.call_callback.clone();
+        let windows = self.windows.clone();
+        let zoom_gesture = GestureZoom::new(&gl_area);
+        // The scale is relative to the beginning of the gesture
+        let last_scale = Rc::new(Cell::new(1.0));
+        let begin_scale = last_scale.clone();
+        zoom_gesture.connect_begin(move |_, _| begin_scale.set(1.0));
+        zoom_gesture.connect_scale_changed(move |_, scale| {
+            let magnification = scale / last_scale.replace(scale);
+            let mut windows = windows.borrow_mut();
+            let window: &mut GtkWindow = windows.get_mut(WINDOW_ID).unwrap();
+            window.view_events.push(ViewEvent::Pinch(magnification as f32));
+            call_callback.set(true);
+        });
+        // The gesture lives as long as the view
+        mem::forget(zoom_gesture);
+
         let call_callback = self.call_callback.clone();
         let windows = self.windows.clone();
         gtk_window.connect_configure_event(move |_, _| {
@@ -285,12 +338,11 @@ impl AppMethods for App {
         let call_callback = self.call_callback.clone();
         let windows = self.windows.clone();
         gtk_window.connect_button_press_event(move |_, event_button| {
-            println!("Button press");
             let mut windows = windows.borrow_mut();
             let window: &mut GtkWindow = windows.get_mut(WINDOW_ID).unwrap();
-            let (x, y) = event_button.get_position();
-            println!("{}, {}", x, y);
-            window.view_events.push(ViewEvent::MouseInput(ElementState::Pressed, MouseButton::Left, x as i32, y as i32));
+            let (x, y) = window.to_device_pixels(event_button.get_position());
+            let button = utils::gtk_button_to_mouse_button(event_button.get_button());
+            window.view_events.push(ViewEvent::MouseInput(ElementState::Pressed, button, x, y));
             call_callback.set(true);
             Inhibit(false)
         });
@@ -298,12 +350,11 @@ impl AppMethods for App {
         let call_callback = self.call_callback.clone();
         let windows = self.windows.clone();
         gtk_window.connect_button_release_event(move |_, event_button| {
-            println!("Button release");
             let mut windows = windows.borrow_mut();
             let window: &mut GtkWindow = windows.get_mut(WINDOW_ID).unwrap();
-            let (x, y) = event_button.get_position();
-            println!("{}, {}", x, y);
-            window.view_events.push(ViewEvent::MouseInput(ElementState::Released, MouseButton::Left, x as i32, y as i32));
+            let (x, y) = window.to_device_pixels(event_button.get_position());
+            let button = utils::gtk_button_to_mouse_button(event_button.get_button());
+            window.view_events.push(ViewEvent::MouseInput(ElementState::Released, button, x, y));
             call_callback.set(true);
             Inhibit(false)
         });
@@ -311,15 +362,69 @@ impl AppMethods for App {
         let call_callback = self.call_callback.clone();
         let windows = self.windows.clone();
         gl_area.connect_motion_notify_event(move |_, event| {
-            let (x, y) = event.get_position();
             let mut windows = windows.borrow_mut();
             let window: &mut GtkWindow = windows.get_mut(WINDOW_ID).unwrap();
-            window.mouse_coordinate = (x as i32, y as i32);
-            window.view_events.push(ViewEvent::MouseMoved(x as i32, y as i32));
+            let (x, y) = window.to_device_pixels(event.get_position());
+            window.mouse_coordinate = (x, y);
+            window.view_events.push(ViewEvent::MouseMoved(x, y));
+            call_callback.set(true);
+            Inhibit(false)
+        });
+
+        let call_callback = self.call_callback.clone();
+        let windows = self.windows.clone();
+        gtk_window.connect_touch_event(move |_, event| {
+            let phase = match event.get_event_type() {
+                gdk::EventType::TouchBegin => TouchPhase::Started,
+                gdk::EventType::TouchUpdate => TouchPhase::Moved,
+                gdk::EventType::TouchEnd => TouchPhase::Ended,
+                gdk::EventType::TouchCancel => TouchPhase::Cancelled,
+                _ => return Inhibit(false),
+            };
+            let event = match event.clone().downcast::<gdk::EventTouch>() {
+                Ok(event) => event,
+                Err(_) => return Inhibit(false),
+            };
+            // Each finger has its own sequence
+            let id = event.as_ref().sequence as u64;
+            let mut windows = windows.borrow_mut();
+            let window: &mut GtkWindow = windows.get_mut(WINDOW_ID).unwrap();
+            let (x, y) = window.to_device_pixels(event.get_position());
+            window.view_events.push(ViewEvent::Touch(id, phase, x, y));
+            call_callback.set(true);
+            Inhibit(false)
+        });
+
+        let call_callback = self.call_callback.clone();
+        let windows = self.windows.clone();
+        gtk_window.connect_focus_in_event(move |_, _| {
+            let mut windows = windows.borrow_mut();
+            let window: &mut GtkWindow = windows.get_mut(WINDOW_ID).unwrap();
+            window.window_events.push(WindowEvent::WindowFocusChanged(true));
             call_callback.set(true);
             Inhibit(false)
         });
 
+        let call_callback = self.call_callback.clone();
+        let windows = self.windows.clone();
+        gtk_window.connect_focus_out_event(move |_, _| {
+            let mut windows = windows.borrow_mut();
+            let window: &mut GtkWindow = windows.get_mut(WINDOW_ID).unwrap();
+            window.window_events.push(WindowEvent::WindowFocusChanged(false));
+            call_callback.set(true);
+            Inhibit(false)
+        });
+
+        // Moving the window to a monitor with a different scale factor
+        let call_callback = self.call_callback.clone();
+        let windows = self.windows.clone();
+        gtk_window.connect_property_scale_factor_notify(move |_| {
+            let mut windows = windows.borrow_mut();
+            let window: &mut GtkWindow = windows.get_mut(WINDOW_ID).unwrap();
+            window.view_events.push(ViewEvent::GeometryDidChange);
+            call_callback.set(true);
+        });
+
         let is_running = self.is_running.clone();
         gtk_window.connect_delete_event(move |_, _| {
             is_running.set(false);
@@ -328,6 +433,12 @@ impl AppMethods for App {
 
         gtk_window.show_all();
 
+        if fullscreen {
+            toolbar.hide();
+            tabs.set_show_tabs(false);
+            gtk_window.fullscreen();
+        }
+
         gl_area.make_current();
 
         epoxy::load_with(|s| {
@@ -354,6 +465,9 @@ impl AppMethods for App {
             key_modifiers: Cell::new(KeyModifiers::empty()),
             last_pressed_key: Cell::new(None),
             tabs,
+            context_menu: None,
+            history: (vec![], 0),
+            history_menu: None,
             view_events: vec![],
             window_events: vec![],
             mouse_coordinate: (0, 0),
@@ -368,6 +482,12 @@ impl AppMethods for App {
         while self.is_running.get() {
             if gtk::events_pending() {
                 gtk::main_iteration();
+                if self.awaken.swap(false, Ordering::SeqCst) {
+                    for window in windows.borrow_mut().iter_mut() {
+                        window.window_events.push(WindowEvent::EventLoopAwaken);
+                    }
+                    self.call_callback.set(true);
+                }
                 if self.call_callback.get() {
                     callback();
                     self.call_callback.set(false);
@@ -422,8 +542,61 @@ impl AppMethods for App {
             glutin::ControlFlow::Continue
         });*/
     }
+
+    fn quit(&self) {
+        self.is_running.set(false);
+    }
 }
 
 fn icon(name: &str) -> Image {
     Image::new_from_file(format!("images/{}.png", name))
 }
+
+// Long-pressing the back or forward button shows the session history
+// entries in that direction. Returns a flag telling the button to ignore
+// the click that ends the long press.
+fn add_history_menu(button: &ToolButton,
+                    back: bool,
+                    windows: Rc<RefCell<Vec<GtkWindow>>>,
+                    call_callback: Rc<Cell<bool>>) -> Rc<Cell<bool>> {
+    let long_pressed = Rc::new(Cell::new(false));
+    let gesture = GestureLongPress::new(button);
+    // The inner button stops the press events before they bubble up
+    gesture.set_propagation_phase(PropagationPhase::Capture);
+    let pressed = long_pressed.clone();
+    gesture.connect_pressed(move |_, _, _| {
+        let menu = Menu::new();
+        {
+            let windows_ref = windows.borrow();
+            let (ref entries, current) = windows_ref[WINDOW_ID].history;
+            let indices: Vec<usize> = if back {
+                (0..current).rev().collect()
+            } else {
+                (current + 1..entries.len()).collect()
+            };
+            if indices.is_empty() {
+                return;
+            }
+            for index in indices {
+                let item = MenuItem::new_with_label(&entries[index]);
+                let windows = windows.clone();
+                let call_callback = call_callback.clone();
+                item.connect_activate(move |_| {
+                    let mut windows = windows.borrow_mut();
+                    let win: &mut GtkWindow = windows.get_mut(WINDOW_ID).unwrap();
+                    win.window_events.push(WindowEvent::DoCommand(WindowCommand::NavigateTo(index)));
+                    call_callback.set(true);
+                });
+                menu.append(&item);
+            }
+        }
+        pressed.set(true);
+        menu.show_all();
+        menu.popup_easy(1, gtk::get_current_event_time());
+        // Keep the menu alive while it's shown
+        windows.borrow_mut()[WINDOW_ID].history_menu = Some(menu);
+    });
+    // The gesture lives as long as the button
+    mem::forget(gesture);
+    long_pressed
+}
@@ -29,12 +29,22 @@ pub struct GtkWindow {
     last_pressed_key: Cell<Option<Key>>,
     mouse_coordinate: (i32, i32),
     tabs: gtk::Notebook,
+    context_menu: Option<gtk::Menu>,
+    // Session history of the current tab, and the current index
+    history: (Vec<String>, usize),
+    history_menu: Option<gtk::Menu>,
     view_events: Vec<ViewEvent>,
     window_events: Vec<WindowEvent>,
 }
 
 impl GtkWindow {
 
+    // GTK events are in logical pixels, Servo expects device pixels.
+    pub fn to_device_pixels(&self, (x, y): (f64, f64)) -> (i32, i32) {
+        let factor = self.gtk_window.get_scale_factor() as f64;
+        ((x * factor).round() as i32, (y * factor).round() as i32)
+    }
+
     pub fn glutin_event_to_command(&self/*, event: &glutin::WindowEvent*/) -> Option<WindowCommand> {
         None
         /*match *event {
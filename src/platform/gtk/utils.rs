@@ -5,7 +5,7 @@
 use gdk;
 use gdk::Display;
 use servo::ServoCursor;
-use traits::view::Key;
+use traits::view::{Key, MouseButton};
 
 pub fn gtk_key_to_script_key(key: gdk::key) -> Result<Key, ()> {
     Err(())
@@ -232,6 +232,14 @@ pub fn servo_cursor_to_gtk_cursor(servo_cursor: ServoCursor) -> gdk::Cursor {
     gdk::Cursor::new_from_name(&display, cursor_name)
 }
 
+pub fn gtk_button_to_mouse_button(button: u32) -> MouseButton {
+    match button {
+        2 => MouseButton::Middle,
+        3 => MouseButton::Right,
+        _ => MouseButton::Left,
+    }
+}
+
 // Some shortcuts use Cmd on Mac and Control on other systems.
 pub fn cmd_or_ctrl(modifiers: gdk::ModifierType) -> bool {
     if cfg!(target_os = "macos") {
@@ -46,8 +46,8 @@ impl ViewMethods for View {
         #[cfg(not(target_os = "windows"))]
         let factor = 1.0f32;
 
-        width /= factor as u32;
-        height /= factor as u32;
+        width = (width as f32 / factor).round() as u32;
+        height = (height as f32 / factor).round() as u32;
 
         println!("Get x, y");
         let (x, y) =
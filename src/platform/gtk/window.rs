@@ -6,6 +6,7 @@ use std::cell::RefCell;
 use std::mem;
 use std::rc::Rc;
 
+use gdk;
 use gdk::{POINTER_MOTION_MASK, SCROLL_MASK};
 use gtk;
 use gtk::{
@@ -13,6 +14,12 @@ use gtk::{
     GLArea,
     GLAreaExt,
     Label,
+    Menu,
+    MenuExt,
+    MenuExtManual,
+    MenuItem,
+    MenuItemExt,
+    MenuShellExt,
     NotebookExt,
     NotebookExtManual,
     WidgetExt,
@@ -21,7 +28,7 @@ use gtk::{
 use logs::ShellLog;
 use platform::View;
 use servo::EventLoopWaker;
-use state::WindowState;
+use state::{Focus, WindowState};
 use super::GtkWindow;
 use traits::view::ViewMethods;
 use traits::window::{WindowCommand, WindowEvent, WindowMethods};
@@ -39,6 +46,48 @@ impl Window {
     }
 }
 
+impl Window {
+    // Outside of any borrow of `windows`: the menu signals borrow it.
+    fn render_context_menu(&self, state: &WindowState) {
+        let shown = self.windows.borrow()[WINDOW_ID].context_menu.is_some();
+        match state.context_menu {
+            Some(ref items) if !shown => {
+                let menu = Menu::new();
+                for item in items {
+                    let menu_item = MenuItem::new_with_label(item.label);
+                    let windows = self.windows.clone();
+                    let cmd = item.command.clone();
+                    menu_item.connect_activate(move |_| {
+                        push_event(&windows, WindowEvent::DoCommand(cmd.clone()));
+                    });
+                    menu.append(&menu_item);
+                }
+                let windows = self.windows.clone();
+                menu.connect_deactivate(move |_| {
+                    push_event(&windows, WindowEvent::ContextMenuClosed);
+                });
+                menu.show_all();
+                menu.popup_easy(3, gtk::get_current_event_time());
+                self.windows.borrow_mut()[WINDOW_ID].context_menu = Some(menu);
+            }
+            None if shown => {
+                let menu = self.windows.borrow_mut()[WINDOW_ID].context_menu.take();
+                if let Some(menu) = menu {
+                    menu.popdown();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn push_event(windows: &Rc<RefCell<Vec<GtkWindow>>>, event: WindowEvent) {
+    let mut windows = windows.borrow_mut();
+    let window = &mut windows[WINDOW_ID];
+    window.window_events.push(event);
+    window.event_loop_waker.wake();
+}
+
 impl WindowMethods for Window {
     fn render(&self, state: &WindowState) {
         // FIXME: mut WindowState
@@ -57,6 +106,11 @@ impl WindowMethods for Window {
         {
             let mut windows = self.windows.borrow_mut();
 
+            if let Some(idx) = state.current_browser_index {
+                let browser = &state.browsers[idx];
+                windows[WINDOW_ID].history = (browser.history.clone(), browser.history_index);
+            }
+
             let state_count = state.browsers.len();
             {
                 let window = &windows[WINDOW_ID];
@@ -69,15 +123,26 @@ impl WindowMethods for Window {
                 }
                 for i in 0..state_count {
                     if let Some(ref title) = state.browsers[i].title {
-                        if Some(i) == state.current_browser_index {
-                            window.gtk_window.set_title(title);
-                        }
                         if let Some(tab) = window.tabs.get_nth_page(Some(i as u32)) {
                             window.tabs.set_tab_label_text(&tab, title);
                         }
                     }
                 }
 
+                if let Some(idx) = state.current_browser_index {
+                    let title = state.browsers[idx].title.as_ref().map_or("", |t| t.as_str());
+                    let title = match (state.private, title.is_empty()) {
+                        (true, true) => "Private".to_owned(),
+                        (true, false) => format!("{} (Private)", title),
+                        (false, _) => title.to_owned(),
+                    };
+                    // No status bar. The zoom level goes in the title.
+                    match state.zoom_indicator {
+                        Some(ref zoom) => window.gtk_window.set_title(&format!("{} - {}", title, zoom)),
+                        None => window.gtk_window.set_title(&title),
+                    }
+                }
+
                 tabs = window.tabs.clone();
                 visual_count = window.tabs.get_n_pages() as usize;
                 if state_count == visual_count + 1 {
@@ -93,14 +158,16 @@ impl WindowMethods for Window {
                 }
             }
 
-            if state.urlbar_focused {
+            if state.focus == Focus::Urlbar {
                 let url = format!("{}", state.browsers[state.current_browser_index.unwrap()]
-                                  .url.as_ref().map_or("", |t| t.as_str()));
+                                  .display_url().map_or("", |t| t.as_str()));
                 windows[WINDOW_ID].window_events.push(WindowEvent::UrlbarFocusChanged(false));
             }
         }
 
         tabs.set_current_page(Some(visual_count as u32));
+
+        self.render_context_menu(state);
     }
 
     fn new_view(&self) -> Result<Rc<ViewMethods>, &'static str> {
@@ -121,6 +188,22 @@ impl WindowMethods for Window {
 
     fn append_logs(&self, _logs: &Vec<ShellLog>) {
     }
+
+    fn supports_clipboard(&self) -> bool {
+        true
+    }
+
+    fn get_clipboard_contents(&self) -> Option<String> {
+        clipboard().wait_for_text()
+    }
+
+    fn set_clipboard_contents(&self, contents: &str) {
+        clipboard().set_text(contents);
+    }
+}
+
+fn clipboard() -> gtk::Clipboard {
+    gtk::Clipboard::get(&gdk::Atom::intern("CLIPBOARD"))
 }
 
 
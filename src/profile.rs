@@ -0,0 +1,192 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Profiles keep everything a browsing session persists in one directory:
+//! Servo's storage (cookies, local storage, HSTS list, caches) and the
+//! shell's own files.
+//!
+//! Named profiles live in the user data directory
+//! (`$XDG_DATA_HOME/servoshell/profiles/<name>` on Linux). A private
+//! profile lives in the temporary directory and is deleted on exit.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_PROFILE: &'static str = "default";
+
+#[derive(Debug)]
+enum ProfileKind {
+    Named(String),
+    Directory,
+    Private,
+}
+
+#[derive(Debug)]
+pub struct Profile {
+    kind: ProfileKind,
+    dir: PathBuf,
+}
+
+impl Profile {
+    /// `--profile` accepts a profile name or a path to a directory.
+    pub fn new(name_or_dir: &str) -> io::Result<Profile> {
+        let is_path = name_or_dir.contains('/') || name_or_dir.contains('\\') ||
+                      name_or_dir.starts_with('.');
+        let profile = if is_path {
+            Profile {
+                kind: ProfileKind::Directory,
+                dir: env::current_dir()?.join(name_or_dir),
+            }
+        } else {
+            let dir = data_dir().ok_or(io::Error::new(io::ErrorKind::NotFound,
+                                                      "Can't find the user data directory"))?;
+            Profile {
+                kind: ProfileKind::Named(name_or_dir.to_owned()),
+                dir: dir.join("profiles").join(name_or_dir),
+            }
+        };
+        fs::create_dir_all(&profile.dir)?;
+        Ok(profile)
+    }
+
+    pub fn private() -> io::Result<Profile> {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| {
+            d.as_secs() * 1_000_000_000 + d.subsec_nanos() as u64
+        }).unwrap_or(0);
+        let dir = env::temp_dir().join(format!("servoshell-private-{:x}", nanos));
+        // Fails if someone created it first
+        create_private_dir(&dir)?;
+        Ok(Profile {
+            kind: ProfileKind::Private,
+            dir: dir,
+        })
+    }
+
+    pub fn is_private(&self) -> bool {
+        match self.kind {
+            ProfileKind::Private => true,
+            _ => false,
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Where the servoshell:// pages are generated. Only the user can
+    /// read it: the pages show logs and open tabs.
+    pub fn pages_dir(&self) -> PathBuf {
+        self.dir.join("pages")
+    }
+
+    pub fn create_pages_dir(&self) -> io::Result<()> {
+        match create_private_dir(&self.pages_dir()) {
+            Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Servo's config dir.
+    pub fn servo_dir(&self) -> PathBuf {
+        self.dir.join("servo")
+    }
+
+    /// Where the remote control socket of this profile lives. None for
+    /// private profiles: they don't accept remote commands, and don't
+    /// forward their URLs to a running browser.
+    pub fn socket_path(&self) -> Option<PathBuf> {
+        match self.kind {
+            ProfileKind::Named(ref name) if name == DEFAULT_PROFILE => {
+                runtime_dir().map(|dir| dir.join("servoshell.sock"))
+            }
+            ProfileKind::Named(ref name) => {
+                runtime_dir().map(|dir| dir.join(format!("servoshell-{}.sock", name)))
+            }
+            ProfileKind::Directory => Some(self.dir.join("servoshell.sock")),
+            ProfileKind::Private => None,
+        }
+    }
+
+    pub fn delete_if_private(&self) {
+        if self.is_private() && self.dir.exists() {
+            if let Err(err) = fs::remove_dir_all(&self.dir) {
+                warn!("Can't remove private profile {:?}: {}", self.dir, err);
+            }
+        }
+    }
+}
+
+impl Drop for Profile {
+    fn drop(&mut self) {
+        self.delete_if_private();
+    }
+}
+
+/// Create a directory only the user can access. Fails if it exists.
+#[cfg(unix)]
+fn create_private_dir(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    fs::DirBuilder::new().mode(0o700).create(path)
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(path: &Path) -> io::Result<()> {
+    fs::DirBuilder::new().create(path)
+}
+
+#[cfg(target_os = "macos")]
+fn data_dir() -> Option<PathBuf> {
+    env::home_dir().map(|home| home.join("Library").join("Application Support").join("ServoShell"))
+}
+
+#[cfg(target_os = "windows")]
+fn data_dir() -> Option<PathBuf> {
+    env::var_os("APPDATA").map(|dir| PathBuf::from(dir).join("ServoShell"))
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn data_dir() -> Option<PathBuf> {
+    env::var_os("XDG_DATA_HOME").map(PathBuf::from)
+        .or_else(|| env::home_dir().map(|home| home.join(".local").join("share")))
+        .map(|dir| dir.join("servoshell"))
+}
+
+/// `$XDG_RUNTIME_DIR`, or a per-user directory in the temporary directory.
+/// None if that directory isn't private to the user.
+#[cfg(unix)]
+fn runtime_dir() -> Option<PathBuf> {
+    use libc;
+    use std::os::unix::fs::MetadataExt;
+
+    if let Some(dir) = env::var_os("XDG_RUNTIME_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    let uid = unsafe { libc::getuid() };
+    let dir = env::temp_dir().join(format!("servoshell-{}", uid));
+    match create_private_dir(&dir) {
+        Err(ref err) if err.kind() != io::ErrorKind::AlreadyExists => {
+            warn!("Can't create {:?}: {}", dir, err);
+            return None;
+        }
+        _ => {}
+    }
+    // Other users can create it first, and get the socket
+    match fs::symlink_metadata(&dir) {
+        Ok(ref metadata) if metadata.is_dir() && metadata.uid() == uid && metadata.mode() & 0o077 == 0 => {
+            Some(dir)
+        }
+        _ => {
+            warn!("Not using {:?}: not a private directory of this user", dir);
+            None
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn runtime_dir() -> Option<PathBuf> {
+    Some(env::temp_dir())
+}
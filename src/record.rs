@@ -0,0 +1,131 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Input sessions, to reproduce layout and input bugs. `--record` saves
+//! the view events and the commands from the UI with their time, and
+//! `--replay` feeds them back at the same pace.
+
+use serde_json;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use traits::view::{DrawableGeometry, ViewEvent};
+use traits::window::WindowCommand;
+
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    pub urls: Vec<String>,
+    pub window_size: (u32, u32),
+    pub view_size: (u32, u32),
+    pub hidpi_factor: f32,
+    pub events: Vec<RecordedInput>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RecordedInput {
+    // Since the beginning of the session
+    pub time_ms: u64,
+    pub input: Input,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Input {
+    View(ViewEvent),
+    Command(WindowCommand),
+}
+
+impl Session {
+    pub fn load(path: &Path) -> Result<Session, String> {
+        let file = File::open(path).map_err(|err| err.to_string())?;
+        serde_json::from_reader(BufReader::new(file)).map_err(|err| err.to_string())
+    }
+}
+
+fn millis_since(start: Instant) -> u64 {
+    let elapsed = start.elapsed();
+    elapsed.as_secs() * 1000 + (elapsed.subsec_nanos() / 1_000_000) as u64
+}
+
+pub struct Recorder {
+    path: PathBuf,
+    start: Instant,
+    session: Session,
+}
+
+impl Recorder {
+    pub fn new(path: PathBuf,
+               urls: Vec<String>,
+               window_size: (u32, u32),
+               geometry: DrawableGeometry) -> Recorder {
+        Recorder {
+            path,
+            start: Instant::now(),
+            session: Session {
+                urls,
+                window_size,
+                view_size: geometry.view_size,
+                hidpi_factor: geometry.hidpi_factor,
+                events: Vec::new(),
+            },
+        }
+    }
+
+    pub fn record(&mut self, input: Input) {
+        let time_ms = millis_since(self.start);
+        self.session.events.push(RecordedInput { time_ms, input });
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let file = File::create(&self.path)?;
+        serde_json::to_writer(BufWriter::new(file), &self.session).map_err(|err| {
+            io::Error::new(io::ErrorKind::Other, err)
+        })
+    }
+}
+
+/// The session is saved when the shell exits, even after a panic.
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        match self.save() {
+            Ok(()) => info!("Session saved to {:?}", self.path),
+            Err(err) => warn!("Can't save the session to {:?}: {}", self.path, err),
+        }
+    }
+}
+
+pub struct Replayer {
+    start: Instant,
+    events: VecDeque<RecordedInput>,
+}
+
+impl Replayer {
+    pub fn new(session: Session) -> Replayer {
+        Replayer {
+            start: Instant::now(),
+            events: session.events.into_iter().collect(),
+        }
+    }
+
+    /// The inputs due by now.
+    pub fn get_events(&mut self) -> Vec<Input> {
+        let now = millis_since(self.start);
+        let mut inputs = vec![];
+        while self.events.front().map_or(false, |event| event.time_ms <= now) {
+            inputs.push(self.events.pop_front().unwrap().input);
+            if self.events.is_empty() {
+                info!("Replay finished");
+            }
+        }
+        inputs
+    }
+
+    /// Time until the next input. None once the session is over.
+    pub fn next_delay(&self) -> Option<Duration> {
+        self.events.front().map(|event| {
+            Duration::from_millis(event.time_ms.saturating_sub(millis_since(self.start)))
+        })
+    }
+}
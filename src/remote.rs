@@ -0,0 +1,315 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Remote control through a local socket.
+//!
+//! Each line sent to the socket is a JSON-RPC 2.0 request, and each
+//! response is written back as one line:
+//!
+//! ```text
+//! → {"jsonrpc": "2.0", "id": 1, "method": "load", "params": {"url": "https://servo.org"}}
+//! ← {"jsonrpc": "2.0", "id": 1, "result": null}
+//! → {"jsonrpc": "2.0", "id": 2, "method": "get_state"}
+//! ← {"jsonrpc": "2.0", "id": 2, "result": {"current_tab": 0, "tabs": [...]}}
+//! ```
+//!
+//! Methods: load {url}, new_tab {url?}, close_tab, reopen_closed_tab,
+//! select_tab {index}, next_tab, prev_tab, reload, stop, back, forward,
+//! navigate_to {index}, zoom_in, zoom_out, zoom_reset, copy_url,
+//! paste_and_go, get_state.
+
+#![cfg_attr(not(unix), allow(dead_code))]
+
+use serde_json::{self, Value};
+use servo::EventLoopWaker;
+use state::WindowState;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use traits::window::WindowCommand;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+// A running instance that doesn't reply by then is considered hung
+const FORWARD_TIMEOUT_SECS: u64 = 5;
+
+#[derive(Clone, Debug)]
+pub enum RemoteCall {
+    Commands(Vec<WindowCommand>),
+    GetState,
+}
+
+/// A request waiting to be answered by the main thread.
+pub struct RemoteRequest {
+    pub call: RemoteCall,
+    id: Value,
+    reply: Sender<String>,
+}
+
+impl RemoteRequest {
+    pub fn reply(self, result: Value) {
+        let response = json!({"jsonrpc": "2.0", "id": self.id, "result": result});
+        self.reply.send(response.to_string()).ok();
+    }
+
+    pub fn reply_error(self, message: &str) {
+        let response = error_response(self.id, INVALID_PARAMS, message);
+        self.reply.send(response).ok();
+    }
+}
+
+pub struct Remote {
+    path: PathBuf,
+    requests: Receiver<RemoteRequest>,
+}
+
+impl Remote {
+    pub fn get_requests(&self) -> Vec<RemoteRequest> {
+        self.requests.try_iter().collect()
+    }
+}
+
+impl Drop for Remote {
+    fn drop(&mut self) {
+        ::std::fs::remove_file(&self.path).ok();
+    }
+}
+
+#[cfg(not(unix))]
+pub fn listen(_path: &Path, _waker: Box<EventLoopWaker>) -> Option<Remote> {
+    warn!("Remote control is only supported on unix");
+    None
+}
+
+#[cfg(not(unix))]
+pub fn forward_urls(_path: &Path, _urls: &[String]) -> bool {
+    false
+}
+
+/// Single instance mode: hand the URLs over to the instance listening on
+/// `path`, to be opened in new tabs. Returns false if no instance is
+/// listening, or if it doesn't reply.
+#[cfg(unix)]
+pub fn forward_urls(path: &Path, urls: &[String]) -> bool {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+    use std::time::Duration;
+
+    let mut stream = match UnixStream::connect(path) {
+        Ok(stream) => stream,
+        Err(_) => return false,
+    };
+    let timeout = Some(Duration::from_secs(FORWARD_TIMEOUT_SECS));
+    if stream.set_read_timeout(timeout).is_err() || stream.set_write_timeout(timeout).is_err() {
+        return false;
+    }
+    let mut responses = match stream.try_clone() {
+        Ok(reader) => BufReader::new(reader).lines(),
+        Err(_) => return false,
+    };
+
+    let requests: Vec<Value> = if urls.is_empty() {
+        vec![json!({"jsonrpc": "2.0", "id": 0, "method": "new_tab"})]
+    } else {
+        urls.iter().enumerate().map(|(id, url)| {
+            json!({"jsonrpc": "2.0", "id": id, "method": "new_tab", "params": {"url": url}})
+        }).collect()
+    };
+
+    for request in requests {
+        if writeln!(stream, "{}", request).is_err() {
+            return false;
+        }
+        let response = match responses.next() {
+            Some(Ok(response)) => response,
+            _ => {
+                eprintln!("The running instance doesn't reply, starting a new one");
+                return false;
+            }
+        };
+        let error = serde_json::from_str::<Value>(&response).ok().and_then(|response| {
+            response.get("error").cloned()
+        });
+        if let Some(error) = error {
+            eprintln!("Running instance failed to open {}: {}", request["params"]["url"], error);
+        }
+    }
+    true
+}
+
+/// Listen for remote commands. The event loop is woken up for each
+/// request. Returns None if the socket can't be created, or if another
+/// instance is already listening.
+#[cfg(unix)]
+pub fn listen(path: &Path, waker: Box<EventLoopWaker>) -> Option<Remote> {
+    use std::fs;
+    use std::io::{BufRead, BufReader, ErrorKind, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::thread;
+
+    let listener = match UnixListener::bind(path) {
+        Ok(listener) => listener,
+        Err(ref err) if err.kind() == ErrorKind::AddrInUse => {
+            if UnixStream::connect(path).is_ok() {
+                warn!("Another instance is listening on {:?}", path);
+                return None;
+            }
+            // Left behind by an instance that didn't exit cleanly
+            fs::remove_file(path).ok();
+            match UnixListener::bind(path) {
+                Ok(listener) => listener,
+                Err(err) => {
+                    warn!("Can't listen on {:?}: {}", path, err);
+                    return None;
+                }
+            }
+        }
+        Err(err) => {
+            warn!("Can't listen on {:?}: {}", path, err);
+            return None;
+        }
+    };
+
+    info!("Listening for remote commands on {:?}", path);
+
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    warn!("Remote connection failed: {}", err);
+                    continue;
+                }
+            };
+            let sender = sender.clone();
+            let waker = waker.clone();
+            thread::spawn(move || {
+                let mut writer = match stream.try_clone() {
+                    Ok(writer) => writer,
+                    Err(_) => return,
+                };
+                for line in BufReader::new(stream).lines() {
+                    let line = match line {
+                        Ok(line) => line,
+                        Err(_) => break,
+                    };
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let response = match parse_request(&line) {
+                        Ok((id, call)) => {
+                            let (reply, response) = mpsc::channel();
+                            let request = RemoteRequest { call, id, reply };
+                            if sender.send(request).is_err() {
+                                break;
+                            }
+                            waker.wake();
+                            match response.recv() {
+                                Ok(response) => response,
+                                Err(_) => break,
+                            }
+                        }
+                        Err(response) => response,
+                    };
+                    if writeln!(writer, "{}", response).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+
+    Some(Remote {
+        path: path.to_owned(),
+        requests: receiver,
+    })
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> String {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {"code": code, "message": message},
+    }).to_string()
+}
+
+/// On failure, returns the error response.
+fn parse_request(line: &str) -> Result<(Value, RemoteCall), String> {
+    let request: Value = serde_json::from_str(line).map_err(|err| {
+        error_response(Value::Null, PARSE_ERROR, &err.to_string())
+    })?;
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let method = match request.get("method").and_then(Value::as_str) {
+        Some(method) => method.to_owned(),
+        None => return Err(error_response(id, INVALID_REQUEST, "Missing method")),
+    };
+    let params = request.get("params").cloned().unwrap_or(Value::Null);
+    match parse_call(&method, &params) {
+        Ok(call) => Ok((id, call)),
+        Err((code, message)) => Err(error_response(id, code, &message)),
+    }
+}
+
+fn parse_call(method: &str, params: &Value) -> Result<RemoteCall, (i64, String)> {
+    let url = params.get("url").and_then(Value::as_str).map(|url| url.to_owned());
+    let commands = match method {
+        "get_state" => return Ok(RemoteCall::GetState),
+        "load" => match url {
+            Some(url) => vec![WindowCommand::Load(url)],
+            None => return Err((INVALID_PARAMS, "Expected a url parameter".to_owned())),
+        },
+        "new_tab" => match url {
+            Some(url) => vec![WindowCommand::NewTab, WindowCommand::Load(url)],
+            None => vec![WindowCommand::NewTab],
+        },
+        "select_tab" => match params.get("index").and_then(Value::as_u64) {
+            Some(index) => vec![WindowCommand::SelectTab(index as usize)],
+            None => return Err((INVALID_PARAMS, "Expected an index parameter".to_owned())),
+        },
+        "navigate_to" => match params.get("index").and_then(Value::as_u64) {
+            Some(index) => vec![WindowCommand::NavigateTo(index as usize)],
+            None => return Err((INVALID_PARAMS, "Expected an index parameter".to_owned())),
+        },
+        "close_tab" => vec![WindowCommand::CloseTab],
+        "reopen_closed_tab" => vec![WindowCommand::ReopenClosedTab],
+        "next_tab" => vec![WindowCommand::NextTab],
+        "prev_tab" => vec![WindowCommand::PrevTab],
+        "reload" => vec![WindowCommand::Reload],
+        "stop" => vec![WindowCommand::Stop],
+        "copy_url" => vec![WindowCommand::CopyUrl],
+        "paste_and_go" => vec![WindowCommand::PasteAndGo],
+        "back" => vec![WindowCommand::NavigateBack],
+        "forward" => vec![WindowCommand::NavigateForward],
+        "zoom_in" => vec![WindowCommand::ZoomIn],
+        "zoom_out" => vec![WindowCommand::ZoomOut],
+        "zoom_reset" => vec![WindowCommand::ZoomToActualSize],
+        _ => return Err((METHOD_NOT_FOUND, format!("Unknown method: {}", method))),
+    };
+    Ok(RemoteCall::Commands(commands))
+}
+
+/// Result of the get_state method.
+pub fn state_to_json(state: &WindowState) -> Value {
+    let tabs: Vec<Value> = state.browsers.iter().map(|browser| {
+        json!({
+            "url": browser.display_url(),
+            "title": browser.title,
+            "is_loading": browser.is_loading,
+            "can_go_back": browser.can_go_back,
+            "can_go_forward": browser.can_go_forward,
+            "history": browser.history,
+            "history_index": browser.history_index,
+            "zoom": browser.zoom,
+            "blocked_navigations": browser.blocked_navigations,
+        })
+    }).collect();
+    json!({
+        "current_tab": state.current_browser_index,
+        "tabs": tabs,
+    })
+}
@@ -0,0 +1,143 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Scrolling. Wheel line deltas can be animated (smooth scrolling), and
+//! touchpad scrolls can keep going once the fingers are lifted (kinetic
+//! scrolling). Animations are driven by `frame`, called every `FRAME_MS`.
+
+use std::time::{Duration, Instant};
+use traits::view::{MouseScrollDelta, TouchPhase};
+
+/// A wheel notch scrolls three lines of 16px text.
+const LINE_HEIGHT: f32 = 3.0 * 16.0 * 1.2;
+
+/// Time between two animation frames.
+pub const FRAME_MS: u64 = 16;
+
+// Part of the remaining distance scrolled at each smooth scrolling frame
+const SMOOTH_STEP: f32 = 0.25;
+// Velocity kept from one kinetic frame to the next
+const FRICTION: f32 = 0.95;
+// Under this speed, in pixels per frame, kinetic scrolling stops
+const MIN_VELOCITY: f32 = 0.5;
+// No momentum if the fingers stopped moving before being lifted
+const MAX_RELEASE_DELAY_MS: u64 = 100;
+
+pub struct Scroller {
+    // Where the scroll started. Animations keep scrolling there.
+    position: (i32, i32),
+    // Distance left to the smooth scrolling animation
+    remaining: (f32, f32),
+    // Kinetic scrolling, in pixels per frame
+    velocity: (f32, f32),
+    // Average of the last touchpad deltas, and when the last one came
+    touchpad_velocity: (f32, f32),
+    last_touchpad_move: Option<Instant>,
+}
+
+impl Scroller {
+    pub fn new() -> Scroller {
+        Scroller {
+            position: (0, 0),
+            remaining: (0.0, 0.0),
+            velocity: (0.0, 0.0),
+            touchpad_velocity: (0.0, 0.0),
+            last_touchpad_move: None,
+        }
+    }
+
+    /// A wheel or touchpad event at `position`. Returns the distance to
+    /// scroll right away. The rest, if any, is returned by `frame`.
+    pub fn scroll(&mut self,
+                  delta: MouseScrollDelta,
+                  phase: &TouchPhase,
+                  position: (i32, i32),
+                  smooth: bool,
+                  kinetic: bool) -> (f32, f32) {
+        self.position = position;
+        match delta {
+            MouseScrollDelta::LineDelta(x, y) => {
+                let (x, y) = (x * LINE_HEIGHT, y * LINE_HEIGHT);
+                self.velocity = (0.0, 0.0);
+                if smooth {
+                    self.remaining = (self.remaining.0 + x, self.remaining.1 + y);
+                    (0.0, 0.0)
+                } else {
+                    (x, y)
+                }
+            }
+            MouseScrollDelta::PixelDelta(x, y) => {
+                self.remaining = (0.0, 0.0);
+                match *phase {
+                    TouchPhase::Started => {
+                        self.velocity = (0.0, 0.0);
+                        self.touchpad_velocity = (x, y);
+                        self.last_touchpad_move = Some(Instant::now());
+                    }
+                    TouchPhase::Moved => {
+                        let (vx, vy) = self.touchpad_velocity;
+                        self.touchpad_velocity = ((vx + x) / 2.0, (vy + y) / 2.0);
+                        self.last_touchpad_move = Some(Instant::now());
+                    }
+                    TouchPhase::Cancelled => {
+                        self.last_touchpad_move = None;
+                    }
+                    TouchPhase::Ended => {
+                        let recent = self.last_touchpad_move.map_or(false, |time| {
+                            time.elapsed() < Duration::from_millis(MAX_RELEASE_DELAY_MS)
+                        });
+                        if kinetic && recent {
+                            self.velocity = self.touchpad_velocity;
+                        }
+                        self.last_touchpad_move = None;
+                    }
+                }
+                (x, y)
+            }
+        }
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.remaining != (0.0, 0.0) || self.velocity != (0.0, 0.0)
+    }
+
+    /// Where to scroll for this animation frame.
+    pub fn position(&self) -> (i32, i32) {
+        self.position
+    }
+
+    /// The distance to scroll for this animation frame.
+    pub fn frame(&mut self) -> (f32, f32) {
+        if self.remaining != (0.0, 0.0) {
+            let (x, y) = self.remaining;
+            let step = |d: f32| if d.abs() < 1.0 { d } else { d * SMOOTH_STEP };
+            let (dx, dy) = (step(x), step(y));
+            self.remaining = (x - dx, y - dy);
+            return (dx, dy);
+        }
+        let (vx, vy) = self.velocity;
+        if vx.abs() < MIN_VELOCITY && vy.abs() < MIN_VELOCITY {
+            self.velocity = (0.0, 0.0);
+            return (0.0, 0.0);
+        }
+        self.velocity = (vx * FRICTION, vy * FRICTION);
+        (vx, vy)
+    }
+
+    /// Clicks and key presses stop the animations.
+    pub fn stop(&mut self) {
+        self.remaining = (0.0, 0.0);
+        self.velocity = (0.0, 0.0);
+    }
+}
+
+/// Ctrl+wheel zoom. A wheel notch, or a touchpad scroll of the same
+/// distance, zooms by 10%.
+pub fn zoom_factor(delta: &MouseScrollDelta) -> f32 {
+    let notches = match *delta {
+        MouseScrollDelta::LineDelta(_, y) => y,
+        MouseScrollDelta::PixelDelta(_, y) => y / LINE_HEIGHT,
+    };
+    1.1f32.powf(notches)
+}
@@ -4,9 +4,11 @@
 
 extern crate servo;
 
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::rc::Rc;
 use std::cell::{Cell, RefCell};
+use std::time::{Duration, Instant};
 
 #[cfg(feature = "force-gtk")]
 use glib_itc::Receiver;
@@ -21,11 +23,11 @@ use self::servo::msg::constellation_msg::TraversalDirection;
 use self::servo::servo_geometry::DeviceIndependentPixel;
 use self::servo::euclid::{Point2D, ScaleFactor, Size2D, TypedPoint2D, TypedRect, TypedSize2D, TypedVector2D};
 use self::servo::ipc_channel::ipc;
-use self::servo::script_traits::{LoadData, MouseButton, TouchEventType};
+use self::servo::script_traits::{LoadData, MouseButton, TouchEventType, TouchId};
 use self::servo::style_traits::DevicePixel;
 use self::servo::net_traits::net_error_list::NetError;
 use self::servo::webrender_api;
-use state::BrowserState;
+use state::{BrowserState, LoadErrorKind};
 
 pub use self::servo::BrowserId;
 pub use self::servo::gl;
@@ -36,12 +38,17 @@ pub use self::servo::compositing::windowing::WebRenderDebugOption;
 pub use self::servo::msg::constellation_msg::{Key, KeyModifiers, KeyState};
 pub use self::servo::msg::constellation_msg::{SHIFT, CONTROL, ALT, SUPER};
 
+use filter::Filter;
+use pages;
 use traits::view::{self, DrawableGeometry};
 
-const SHELL_ISSUE_ALIAS: &'static str = "servoshell://issue/servoshell";
-const SERVO_ISSUE_ALIAS: &'static str = "servoshell://issue/servo";
-const SHELL_ISSUE_URL: &'static str = "http://github.com/paulrouget/servoshell/issues/new";
-const SERVO_ISSUE_URL: &'static str  = "http://github.com/servo/servo/issues/new";
+// Titles of servo_resources/neterror.html and badcert.html
+const NETWORK_ERROR_TITLE: &'static str = "Servo: error loading page";
+const CERTIFICATE_ERROR_TITLE: &'static str = "Servo: certificate error";
+
+// Servo can send events for a browser after it's closed. It's forgotten
+// once none came for this long.
+const CLOSED_BROWSER_QUIET_SECS: u64 = 10;
 
 pub enum ServoEvent {
     SetWindowInnerSize(u32, u32),
@@ -57,6 +64,39 @@ pub enum ServoEvent {
     FaviconChanged(BrowserId, ServoUrl),
     Key(Option<char>, Key, KeyModifiers),
     OpenInDefaultBrowser(&'static str),
+    ShellPageRequested(BrowserId, ServoUrl),
+    LoadRequested(BrowserId, ServoUrl),
+    LoadFailed(BrowserId, LoadErrorKind),
+    // URL, and the filter rule that blocked it
+    NavigationBlocked(BrowserId, ServoUrl, String),
+    // Navigation denied because it follows a middle or Ctrl/Cmd click
+    LoadInBackgroundTab(ServoUrl),
+}
+
+impl ServoEvent {
+    /// The browser this event is about, if any.
+    pub fn browser_id(&self) -> Option<BrowserId> {
+        match *self {
+            ServoEvent::TitleChanged(id, _) |
+            ServoEvent::LoadStart(id) |
+            ServoEvent::LoadEnd(id) |
+            ServoEvent::HeadParsed(id) |
+            ServoEvent::HistoryChanged(id, _, _) |
+            ServoEvent::FaviconChanged(id, _) |
+            ServoEvent::ShellPageRequested(id, _) |
+            ServoEvent::LoadRequested(id, _) |
+            ServoEvent::LoadFailed(id, _) |
+            ServoEvent::NavigationBlocked(id, _, _) => Some(id),
+            _ => None,
+        }
+    }
+}
+
+// A middle or Ctrl/Cmd click on a link
+struct NewTabRequest {
+    browser: BrowserId,
+    link: ServoUrl,
+    time: Instant,
 }
 
 struct LastMouseDown {
@@ -71,26 +111,59 @@ pub struct Servo {
     servo: Rc<RefCell<servo::Servo<ServoCallbacks>>>,
     callbacks: Rc<ServoCallbacks>,
     mouse_down: RefCell<Option<LastMouseDown>>,
+    // Servo sends key events to the selected browser
+    focused_browser: Cell<Option<BrowserId>>,
+    // Keys pressed and not released yet, released on blur
+    pressed_keys: RefCell<Vec<(Key, KeyModifiers)>>,
 }
 
 impl Servo {
 
-    pub fn configure(path: PathBuf) {
+    /// `args` are Servo's command line arguments, without the executable
+    /// name. Servo exits with a usage message if an argument is invalid.
+    pub fn configure(path: PathBuf, args: Vec<String>) {
         let path = path.to_str().unwrap().to_string();
         set_resources_path(Some(path));
-        // FIXME: I forgot… is that necessary now?
-        opts::set_defaults(opts::default_opts());
+
+        let mut args = args;
+        args.insert(0, env!("CARGO_PKG_NAME").to_owned());
+        if let opts::ArgumentParsingResult::ContentProcess(_) = opts::from_cmdline_args(&args) {
+            warn!("--content-process is not a valid Servo argument for the main process");
+        }
+
+        let opts = opts::get();
+        info!("Servo options: multiprocess: {}, sandbox: {}, devtools port: {:?}, \
+               webdriver port: {:?}, hard fail: {}, user stylesheets: {}, config dir: {:?}, \
+               user agent: {}",
+              opts.multiprocess, opts.sandbox, opts.devtools_port, opts.webdriver_port,
+              opts.hard_fail, opts.user_stylesheets.len(), opts.config_dir, opts.user_agent);
+    }
+
+    pub fn run_content_process(token: String) {
+        servo::run_content_process(token)
     }
 
     pub fn version(&self) -> String {
         servo_version()
     }
 
-    pub fn new(geometry: DrawableGeometry, view: Rc<view::ViewMethods>, waker: Box<EventLoopWaker>)
+    /// With `supports_clipboard`, Servo handles copy and paste in web
+    /// content itself.
+    pub fn new(geometry: DrawableGeometry,
+               view: Rc<view::ViewMethods>,
+               waker: Box<EventLoopWaker>,
+               supports_clipboard: bool)
         -> Servo
     {
         let callbacks = Rc::new(ServoCallbacks {
+            supports_clipboard,
             event_queue: RefCell::new(Vec::new()),
+            filter: RefCell::new(Filter::new()),
+            allowlist: RefCell::new(None),
+            new_tab_requested: RefCell::new(None),
+            closed_browsers: RefCell::new(HashMap::new()),
+            traversing: RefCell::new(HashSet::new()),
+            frames: RefCell::new(Vec::new()),
             geometry: Cell::new(geometry),
             waker: waker,
             view: view.clone(),
@@ -103,9 +176,43 @@ impl Servo {
             servo,
             callbacks: callbacks,
             mouse_down: RefCell::new(None),
+            focused_browser: Cell::new(None),
+            pressed_keys: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// The navigation of this browser to the link, if it starts within a
+    /// second, is denied and reported as `LoadInBackgroundTab`. Servo
+    /// doesn't tell which click caused a navigation, so the shell flags
+    /// the click on the hovered link itself.
+    pub fn open_next_navigation_in_new_tab(&self, id: BrowserId, link: ServoUrl) {
+        *self.callbacks.new_tab_requested.borrow_mut() = Some(NewTabRequest {
+            browser: id,
+            link: link,
+            time: Instant::now(),
+        });
+    }
+
+    /// The button was released away from the link: no navigation to
+    /// open in a new tab.
+    pub fn cancel_new_tab_unless_over(&self, link: Option<&ServoUrl>) {
+        let mut request = self.callbacks.new_tab_requested.borrow_mut();
+        if request.as_ref().map_or(false, |request| Some(&request.link) != link) {
+            *request = None;
         }
     }
 
+    /// Navigations blocked by this filter are denied.
+    pub fn set_filter(&self, filter: Filter) {
+        *self.callbacks.filter.borrow_mut() = filter;
+    }
+
+    /// Only navigations matching a rule of the allowlist are allowed. The
+    /// filter is checked first.
+    pub fn set_allowlist(&self, allowlist: Filter) {
+        *self.callbacks.allowlist.borrow_mut() = Some(allowlist);
+    }
+
     #[cfg(feature = "force-gtk")]
     pub fn connect(&self, rx: &mut Receiver) {
         let servo = self.servo.clone();
@@ -126,48 +233,78 @@ impl Servo {
         self.select_browser(id);
         self.sync(false);
 
-        BrowserState {
-            id: id,
-            zoom: 1.0,
-            url: None,
-            title: None,
-            user_input: None,
-            can_go_back: false,
-            can_go_forward: false,
-            is_loading: false,
-        }
+        BrowserState::new(id)
     }
 
     pub fn get_events(&self) -> Vec<ServoEvent> {
         self.callbacks.get_events()
     }
 
+    /// When frames were presented since the last call.
+    pub fn take_frames(&self) -> Vec<Instant> {
+        let mut frames = self.callbacks.frames.borrow_mut();
+        let copy = frames.drain(..).collect();
+        copy
+    }
+
     pub fn select_browser(&self, id: BrowserId) {
+        // Key events go to the selected browser. The keys held down in the
+        // previous one are released there first.
+        if self.focused_browser.get().map_or(false, |focused| focused != id) {
+            self.blur();
+        }
+        self.focused_browser.set(Some(id));
         let event = WindowEvent::SelectBrowser(id);
         self.events_for_servo.borrow_mut().push(event);
     }
 
+    /// Key events now go to this browser.
+    pub fn focus(&self, id: BrowserId) {
+        if self.focused_browser.get() != Some(id) {
+            self.select_browser(id);
+        }
+    }
+
+    /// Key events don't go to the pages anymore. Servo has no blur event,
+    /// so the keys still held down are released, to not leave them stuck.
+    pub fn blur(&self) {
+        let mut events = self.events_for_servo.borrow_mut();
+        let mut pressed_keys = self.pressed_keys.borrow_mut();
+        for (key, mods) in pressed_keys.drain(..) {
+            events.push(WindowEvent::KeyEvent(None, key, KeyState::Released, mods));
+        }
+    }
+
     pub fn close_browser(&self, id: BrowserId) {
+        if self.focused_browser.get() == Some(id) {
+            self.focused_browser.set(None);
+            self.pressed_keys.borrow_mut().clear();
+        }
+        self.callbacks.closed_browsers.borrow_mut().insert(id, Instant::now());
         let event = WindowEvent::CloseBrowser(id);
         self.events_for_servo.borrow_mut().push(event);
     }
 
     pub fn reload(&self, id: BrowserId) {
+        self.callbacks.traversing.borrow_mut().remove(&id);
         let event = WindowEvent::Reload(id);
         self.events_for_servo.borrow_mut().push(event);
     }
 
-    pub fn go_back(&self, id: BrowserId) {
-        let event = WindowEvent::Navigation(id, TraversalDirection::Back(1));
+    pub fn go_back(&self, id: BrowserId, steps: usize) {
+        self.callbacks.traversing.borrow_mut().insert(id);
+        let event = WindowEvent::Navigation(id, TraversalDirection::Back(steps));
         self.events_for_servo.borrow_mut().push(event);
     }
 
-    pub fn go_forward(&self, id: BrowserId) {
-        let event = WindowEvent::Navigation(id, TraversalDirection::Forward(1));
+    pub fn go_forward(&self, id: BrowserId, steps: usize) {
+        self.callbacks.traversing.borrow_mut().insert(id);
+        let event = WindowEvent::Navigation(id, TraversalDirection::Forward(steps));
         self.events_for_servo.borrow_mut().push(event);
     }
 
     pub fn load_url(&self, id: BrowserId, url: ServoUrl) {
+        self.callbacks.traversing.borrow_mut().remove(&id);
         let event = WindowEvent::LoadUrl(id, url);
         self.events_for_servo.borrow_mut().push(event);
     }
@@ -175,11 +312,9 @@ impl Servo {
     fn substract_margins(&self, x: i32, y: i32) -> (i32, i32) {
         let geometry = self.callbacks.geometry.get();
         let (top, _, _, left) = geometry.margins;
-        let top = top as f32 * geometry.hidpi_factor;
-        let left = left as f32 * geometry.hidpi_factor;
-        let x = x - left as i32;
-        let y = y - top as i32;
-        (x, y)
+        let top = (top as f32 * geometry.hidpi_factor).round() as i32;
+        let left = (left as f32 * geometry.hidpi_factor).round() as i32;
+        (x - left, y - top)
     }
 
     pub fn perform_mouse_move(&self, x: i32, y: i32) {
@@ -197,11 +332,24 @@ impl Servo {
             view::TouchPhase::Started => TouchEventType::Down,
             view::TouchPhase::Moved => TouchEventType::Move,
             view::TouchPhase::Ended => TouchEventType::Up,
+            view::TouchPhase::Cancelled => TouchEventType::Cancel,
         };
         let event = WindowEvent::Scroll(scroll_location, TypedPoint2D::new(x, y), phase);
         self.events_for_servo.borrow_mut().push(event);
     }
 
+    pub fn perform_touch(&self, id: u64, phase: view::TouchPhase, x: i32, y: i32) {
+        let (x, y) = self.substract_margins(x, y);
+        let event_type = match phase {
+            view::TouchPhase::Started => TouchEventType::Down,
+            view::TouchPhase::Moved => TouchEventType::Move,
+            view::TouchPhase::Ended => TouchEventType::Up,
+            view::TouchPhase::Cancelled => TouchEventType::Cancel,
+        };
+        let event = WindowEvent::Touch(event_type, TouchId(id as i32), TypedPoint2D::new(x as f32, y as f32));
+        self.events_for_servo.borrow_mut().push(event);
+    }
+
     pub fn update_geometry(&self, geometry: DrawableGeometry) {
         self.callbacks.geometry.set(geometry);
         let event = WindowEvent::Resize(self.callbacks.framebuffer_size());
@@ -255,6 +403,10 @@ impl Servo {
 
     }
 
+    pub fn pinch_zoom(&self, magnification: f32) {
+        self.events_for_servo.borrow_mut().push(WindowEvent::PinchZoom(magnification));
+    }
+
     pub fn reset_zoom(&self) {
         // FIXME: Why is that useful? Compared to Zoom(1)
         self.events_for_servo.borrow_mut().push(WindowEvent::ResetZoom);
@@ -264,8 +416,15 @@ impl Servo {
         self.events_for_servo.borrow_mut().push(WindowEvent::ToggleWebRenderDebug(option));
     }
 
-    pub fn send_key(&self, _id: BrowserId, c: Option<char>, key: Key, state: KeyState, mods: KeyModifiers) {
-        // FIXME: we should pass the browser id
+    pub fn send_key(&self, id: BrowserId, c: Option<char>, key: Key, state: KeyState, mods: KeyModifiers) {
+        self.focus(id);
+        {
+            let mut pressed_keys = self.pressed_keys.borrow_mut();
+            pressed_keys.retain(|&(k, _)| k != key);
+            if state != KeyState::Released {
+                pressed_keys.push((key, mods));
+            }
+        }
         self.events_for_servo.borrow_mut().push(WindowEvent::KeyEvent(c, key, state, mods));
     }
 
@@ -280,9 +439,51 @@ impl Servo {
     }
 }
 
+// Scale factors can be fractional (1.25, 1.5…). Rounding keeps the
+// framebuffer and the margins aligned with the view.
+fn to_device_pixels(length: u32, hidpi_factor: f32) -> u32 {
+    (length as f32 * hidpi_factor).round() as u32
+}
+
+/// load_error is never called. Failed loads are detected through the
+/// pages Servo shows instead. Servo navigates to about:not-found and
+/// about:failure.
+fn load_error_kind(url: &ServoUrl) -> Option<LoadErrorKind> {
+    match url.as_str() {
+        "about:not-found" => Some(LoadErrorKind::NotFound),
+        "about:failure" => Some(LoadErrorKind::Crash),
+        _ => None,
+    }
+}
+
+/// The network and certificate error pages are shown in place, at the
+/// URL that failed. Their titles, in servo_resources/, tell them apart.
+fn error_page_kind(title: &str) -> Option<LoadErrorKind> {
+    match title {
+        NETWORK_ERROR_TITLE => Some(LoadErrorKind::Network),
+        CERTIFICATE_ERROR_TITLE => Some(LoadErrorKind::Certificate),
+        _ => None,
+    }
+}
+
 struct ServoCallbacks {
     pub geometry: Cell<DrawableGeometry>,
     event_queue: RefCell<Vec<ServoEvent>>,
+    // Checked synchronously in allow_navigation
+    filter: RefCell<Filter>,
+    allowlist: RefCell<Option<Filter>>,
+    // Set on middle and Ctrl/Cmd clicks
+    new_tab_requested: RefCell<Option<NewTabRequest>>,
+    // Events still coming for these browsers are dropped. The last time
+    // one came, to forget the browser once Servo is done with it.
+    closed_browsers: RefCell<HashMap<BrowserId, Instant>>,
+    // Going back or forward, until a load ends or another one starts. A
+    // failed entry reached this way isn't reported again: its error page
+    // would be pushed in front of it again, and the user couldn't go back.
+    traversing: RefCell<HashSet<BrowserId>>,
+    supports_clipboard: bool,
+    // When frames were presented, since the last take_frames
+    frames: RefCell<Vec<Instant>>,
     waker: Box<EventLoopWaker>,
     view: Rc<view::ViewMethods>,
 }
@@ -292,9 +493,29 @@ impl ServoCallbacks {
         // FIXME: ports/glutin/window.rs uses mem::replace. Should we too?
         // See: https://doc.rust-lang.org/core/mem/fn.replace.html
         let mut events = self.event_queue.borrow_mut();
-        let copy = events.drain(..).collect();
+        let mut closed_browsers = self.closed_browsers.borrow_mut();
+        let now = Instant::now();
+        let copy = events.drain(..).filter(|event| {
+            match event.browser_id().and_then(|id| closed_browsers.get_mut(&id)) {
+                Some(last_event) => {
+                    *last_event = now;
+                    false
+                }
+                None => true,
+            }
+        }).collect();
+        let quiet = Duration::from_secs(CLOSED_BROWSER_QUIET_SECS);
+        closed_browsers.retain(|_, last_event| now.duration_since(*last_event) < quiet);
         copy
     }
+
+    fn load_failed(&self, id: BrowserId, error: LoadErrorKind) {
+        if self.traversing.borrow().contains(&id) {
+            info!("Went back or forward to a failed load: {:?}", error);
+            return;
+        }
+        self.event_queue.borrow_mut().push(ServoEvent::LoadFailed(id, error));
+    }
 }
 
 impl WindowMethods for ServoCallbacks {
@@ -304,22 +525,43 @@ impl WindowMethods for ServoCallbacks {
     }
 
     fn supports_clipboard(&self) -> bool {
-        // FIXME
-        false
+        self.supports_clipboard
     }
 
-    fn allow_navigation(&self, _id: BrowserId, url: ServoUrl, chan: ipc::IpcSender<bool>) {
-        if url.as_str() == SHELL_ISSUE_ALIAS {
-            let event = ServoEvent::OpenInDefaultBrowser(SHELL_ISSUE_URL);
-            self.event_queue.borrow_mut().push(event);
-            chan.send(false).ok();
-        } else if url.as_str() == SERVO_ISSUE_ALIAS {
-            let event = ServoEvent::OpenInDefaultBrowser(SERVO_ISSUE_URL);
+    fn allow_navigation(&self, id: BrowserId, url: ServoUrl, chan: ipc::IpcSender<bool>) {
+        if pages::page_path(&url).is_none() {
+            let blocked_by = self.filter.borrow().check(&url).map(|rule| rule.text.clone()).or_else(|| {
+                match *self.allowlist.borrow() {
+                    Some(ref allowlist) if allowlist.check(&url).is_none() => Some("kiosk allowlist".to_owned()),
+                    _ => None,
+                }
+            });
+            let new_tab = blocked_by.is_none() && self.new_tab_requested.borrow().as_ref().map_or(false, |request| {
+                request.browser == id && request.link == url && request.time.elapsed() < Duration::from_secs(1)
+            });
+            if new_tab {
+                *self.new_tab_requested.borrow_mut() = None;
+            }
+            let allowed = blocked_by.is_none() && !new_tab;
+            let event = match blocked_by {
+                Some(rule) => ServoEvent::NavigationBlocked(id, url, rule),
+                None if new_tab => ServoEvent::LoadInBackgroundTab(url),
+                None => {
+                    self.traversing.borrow_mut().remove(&id);
+                    ServoEvent::LoadRequested(id, url)
+                }
+            };
             self.event_queue.borrow_mut().push(event);
-            chan.send(false).ok();
-        } else {
-            chan.send(true).ok();
+            chan.send(allowed).ok();
+            return;
         }
+        // servoshell:// pages are generated by the shell
+        let event = match pages::find(&url).map(|page| &page.handler) {
+            Some(&pages::PageHandler::External(external)) => ServoEvent::OpenInDefaultBrowser(external),
+            _ => ServoEvent::ShellPageRequested(id, url),
+        };
+        self.event_queue.borrow_mut().push(event);
+        chan.send(false).ok();
     }
 
     fn create_event_loop_waker(&self) -> Box<EventLoopWaker> {
@@ -336,20 +578,21 @@ impl WindowMethods for ServoCallbacks {
     }
 
     fn framebuffer_size(&self) -> TypedSize2D<u32, DevicePixel> {
-        let scale_factor = self.geometry.get().hidpi_factor as u32;
-        let (width, height) = self.geometry.get().view_size;
-        TypedSize2D::new(scale_factor * width, scale_factor * height)
+        let geometry = self.geometry.get();
+        let (width, height) = geometry.view_size;
+        TypedSize2D::new(to_device_pixels(width, geometry.hidpi_factor),
+                         to_device_pixels(height, geometry.hidpi_factor))
     }
 
     fn window_rect(&self) -> TypedRect<u32, DevicePixel> {
-        let scale_factor = self.geometry.get().hidpi_factor as u32;
+        let geometry = self.geometry.get();
         let mut size = self.framebuffer_size();
 
-        let (top, right, bottom, left) = self.geometry.get().margins;
-        let top = top * scale_factor;
-        let right = right * scale_factor;
-        let bottom = bottom * scale_factor;
-        let left = left * scale_factor;
+        let (top, right, bottom, left) = geometry.margins;
+        let top = to_device_pixels(top, geometry.hidpi_factor);
+        let right = to_device_pixels(right, geometry.hidpi_factor);
+        let bottom = to_device_pixels(bottom, geometry.hidpi_factor);
+        let left = to_device_pixels(left, geometry.hidpi_factor);
 
         size.height = size.height - top - bottom;
         size.width = size.width - left - right;
@@ -386,10 +629,15 @@ impl WindowMethods for ServoCallbacks {
 
     fn present(&self) {
         self.view.swap_buffers();
+        self.frames.borrow_mut().push(Instant::now());
     }
 
     fn set_page_title(&self, id: BrowserId, title: Option<String>) {
+        let error = title.as_ref().and_then(|title| error_page_kind(title));
         self.event_queue.borrow_mut().push(ServoEvent::TitleChanged(id, title));
+        if let Some(error) = error {
+            self.load_failed(id, error);
+        }
     }
 
     fn status(&self, _id: BrowserId, status: Option<String>) {
@@ -401,6 +649,7 @@ impl WindowMethods for ServoCallbacks {
     }
 
     fn load_end(&self, id: BrowserId) {
+        self.traversing.borrow_mut().remove(&id);
         self.event_queue.borrow_mut().push(ServoEvent::LoadEnd(id));
     }
 
@@ -413,7 +662,11 @@ impl WindowMethods for ServoCallbacks {
     }
 
     fn history_changed(&self, id: BrowserId, entries: Vec<LoadData>, current: usize) {
+        let error = load_error_kind(&entries[current].url);
         self.event_queue.borrow_mut().push(ServoEvent::HistoryChanged(id, entries, current));
+        if let Some(error) = error {
+            self.load_failed(id, error);
+        }
     }
 
     fn set_cursor(&self, cursor: ServoCursor) {
@@ -0,0 +1,48 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Keyboard shortcuts. Keys are sent to the page first, and Servo sends
+//! back the ones the page didn't consume. Only then the shortcut runs.
+//! The platforms handle the few reserved shortcuts (close tab, quit)
+//! themselves, before the page sees them.
+
+use traits::view::{Key, KeyModifiers, CONTROL, SHIFT, SUPER};
+use traits::window::WindowCommand;
+
+fn cmd_or_ctrl(mods: KeyModifiers) -> bool {
+    if cfg!(target_os = "macos") {
+        mods.contains(SUPER)
+    } else {
+        mods.contains(CONTROL)
+    }
+}
+
+/// The command this key runs if the page doesn't consume it.
+pub fn command(key: Key, mods: KeyModifiers) -> Option<WindowCommand> {
+    match (key, cmd_or_ctrl(mods), mods.contains(CONTROL), mods.contains(SHIFT)) {
+        (Key::R, true, _, _) => Some(WindowCommand::Reload),
+        (Key::Left, true, _, _) => Some(WindowCommand::NavigateBack),
+        (Key::Right, true, _, _) => Some(WindowCommand::NavigateForward),
+        (Key::L, true, _, _) => Some(WindowCommand::OpenLocation),
+        (Key::C, true, _, true) => Some(WindowCommand::CopyUrl),
+        (Key::V, true, _, true) => Some(WindowCommand::PasteAndGo),
+        (Key::Equal, true, _, _) => Some(WindowCommand::ZoomIn),
+        (Key::Minus, true, _, _) => Some(WindowCommand::ZoomOut),
+        (Key::Num0, true, _, _) => Some(WindowCommand::ZoomToActualSize),
+        (Key::T, true, _, true) => Some(WindowCommand::ReopenClosedTab),
+        (Key::T, true, _, _) => Some(WindowCommand::NewTab),
+        (Key::Tab, _, true, false) => Some(WindowCommand::NextTab),
+        (Key::Tab, _, true, true) => Some(WindowCommand::PrevTab),
+        (Key::Num1, true, _, _) => Some(WindowCommand::SelectTab(0)),
+        (Key::Num2, true, _, _) => Some(WindowCommand::SelectTab(1)),
+        (Key::Num3, true, _, _) => Some(WindowCommand::SelectTab(2)),
+        (Key::Num4, true, _, _) => Some(WindowCommand::SelectTab(3)),
+        (Key::Num5, true, _, _) => Some(WindowCommand::SelectTab(4)),
+        (Key::Num6, true, _, _) => Some(WindowCommand::SelectTab(5)),
+        (Key::Num7, true, _, _) => Some(WindowCommand::SelectTab(6)),
+        (Key::Num8, true, _, _) => Some(WindowCommand::SelectTab(7)),
+        (Key::Num9, true, _, _) => Some(WindowCommand::SelectTab(8)),
+        _ => None
+    }
+}
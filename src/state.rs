@@ -3,6 +3,9 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use servo::{ServoCursor, BrowserId};
+use traits::window::WindowCommand;
+
+const MAX_CLOSED_TABS: usize = 10;
 
 #[derive(Clone, PartialEq)]
 pub struct AppState {
@@ -27,11 +30,20 @@ pub struct WindowState {
     pub browsers: Vec<BrowserState>,
     pub sidebar_is_open: bool,
     pub logs_visible: bool,
+    pub smooth_scrolling: bool,
+    pub kinetic_scrolling: bool,
     pub debug_options: DebugOptions,
     pub status: Option<String>,
-    pub urlbar_focused: bool,
+    // Shown in place of the status for a moment after zooming
+    pub zoom_indicator: Option<String>,
+    pub focus: Focus,
     pub options_open: bool,
     pub title: String,
+    // Browsing with a temporary profile
+    pub private: bool,
+    pub context_menu: Option<Vec<ContextMenuItem>>,
+    // Most recently closed last
+    pub closed_tabs: Vec<ClosedTab>,
 }
 
 impl WindowState {
@@ -41,10 +53,17 @@ impl WindowState {
             browsers: Vec::new(),
             sidebar_is_open: false,
             logs_visible: false,
+            smooth_scrolling: true,
+            // macOS already sends momentum events
+            kinetic_scrolling: cfg!(not(target_os = "macos")),
             status: None,
-            urlbar_focused: false,
+            zoom_indicator: None,
+            focus: Focus::Urlbar,
             options_open: false,
             title: "ServoShell".to_owned(),
+            private: false,
+            context_menu: None,
+            closed_tabs: Vec::new(),
             debug_options: DebugOptions {
                 show_fragment_borders: false,
                 parallel_display_list_building: false,
@@ -57,12 +76,46 @@ impl WindowState {
             },
         }
     }
+
+    /// Only the last MAX_CLOSED_TABS tabs are kept.
+    pub fn push_closed_tab(&mut self, tab: ClosedTab) {
+        if self.closed_tabs.len() == MAX_CLOSED_TABS {
+            self.closed_tabs.remove(0);
+        }
+        self.closed_tabs.push(tab);
+    }
+}
+
+/// Where key events go
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Focus {
+    Urlbar,
+    Browser(BrowserId),
+    // The context menu
+    Overlay,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct ClosedTab {
+    // Position in the tab list
+    pub index: usize,
+    pub url: String,
+    pub title: Option<String>,
+    pub zoom: f32,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct ContextMenuItem {
+    pub label: &'static str,
+    pub command: WindowCommand,
 }
 
 #[derive(Clone, PartialEq)]
 pub struct BrowserState {
     pub id: BrowserId,
     pub zoom: f32,
+    // Visual zoom from pinch gestures. Doesn't change the layout.
+    pub pinch_zoom: f32,
     pub url: Option<String>,
     pub title: Option<String>,
     // FIXME: pub favicon: Option<>,
@@ -70,6 +123,59 @@ pub struct BrowserState {
     pub can_go_back: bool,
     pub can_go_forward: bool,
     pub is_loading: bool,
+    // Last navigation allowed in this browser, not necessarily committed yet
+    pub requested_url: Option<String>,
+    pub load_error: Option<LoadError>,
+    // Navigations denied by the filter
+    pub blocked_navigations: u32,
+    // URLs of the session history, from the last HistoryChanged
+    pub history: Vec<String>,
+    pub history_index: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoadErrorKind {
+    Network,
+    Certificate,
+    NotFound,
+    Crash,
+    Timeout,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoadError {
+    pub kind: LoadErrorKind,
+    pub url: String,
+}
+
+impl BrowserState {
+    pub fn new(id: BrowserId) -> BrowserState {
+        BrowserState {
+            id: id,
+            zoom: 1.0,
+            pinch_zoom: 1.0,
+            url: None,
+            title: None,
+            user_input: None,
+            can_go_back: false,
+            can_go_forward: false,
+            is_loading: false,
+            requested_url: None,
+            load_error: None,
+            blocked_navigations: 0,
+            history: Vec::new(),
+            history_index: 0,
+        }
+    }
+
+    /// The URL shown to the user. While an error is shown, the URL
+    /// that failed to load.
+    pub fn display_url(&self) -> Option<&String> {
+        match self.load_error {
+            Some(ref error) => Some(&error.url),
+            None => self.url.as_ref(),
+        }
+    }
 }
 
 #[derive(Clone, PartialEq)]
@@ -0,0 +1,77 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Delayed events. Each timer wakes up the event loop when it fires,
+//! and its event is then returned by `get_events`.
+
+use servo::{BrowserId, EventLoopWaker};
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TimerId(usize);
+
+#[derive(Clone, Debug)]
+pub enum TimerEvent {
+    LoadTimeout(BrowserId),
+    KioskIdle,
+    KioskRotate,
+    ScrollFrame,
+    HideZoomIndicator,
+    ReplayNext,
+    BenchmarkNext,
+}
+
+pub struct Timers {
+    next_id: Cell<usize>,
+    cancelled: RefCell<HashSet<TimerId>>,
+    sender: Sender<(TimerId, TimerEvent)>,
+    receiver: Receiver<(TimerId, TimerEvent)>,
+    waker: Box<EventLoopWaker>,
+}
+
+impl Timers {
+    pub fn new(waker: Box<EventLoopWaker>) -> Timers {
+        let (sender, receiver) = mpsc::channel();
+        Timers {
+            next_id: Cell::new(0),
+            cancelled: RefCell::new(HashSet::new()),
+            sender: sender,
+            receiver: receiver,
+            waker: waker,
+        }
+    }
+
+    pub fn schedule(&self, delay: Duration, event: TimerEvent) -> TimerId {
+        let id = TimerId(self.next_id.get());
+        self.next_id.set(id.0 + 1);
+        let sender = self.sender.clone();
+        let waker = self.waker.clone();
+        thread::spawn(move || {
+            thread::sleep(delay);
+            if sender.send((id, event)).is_ok() {
+                waker.wake();
+            }
+        });
+        id
+    }
+
+    pub fn cancel(&self, id: TimerId) {
+        self.cancelled.borrow_mut().insert(id);
+    }
+
+    pub fn get_events(&self) -> Vec<TimerEvent> {
+        let mut cancelled = self.cancelled.borrow_mut();
+        self.receiver.try_iter().filter_map(|(id, event)| {
+            if cancelled.remove(&id) {
+                None
+            } else {
+                Some(event)
+            }
+        }).collect()
+    }
+}
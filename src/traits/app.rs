@@ -25,9 +25,12 @@ pub enum AppCommand {
 
 pub trait AppMethods {
     fn new<'a>() -> Result<Self, &'a str> where Self: Sized;
-    fn new_window<'a>(&self) -> Result<Box<WindowMethods>, &'a str>;
+    /// A fullscreen window has no chrome.
+    fn new_window<'a>(&self, size: (u32, u32), fullscreen: bool) -> Result<Box<WindowMethods>, &'a str>;
     fn get_resources_path() -> Option<PathBuf>;
     fn render(&self, state: &AppState);
     fn get_events(&self) -> Vec<AppEvent>;
     fn run<T>(&self, callback: T) where T: FnMut();
+    /// Makes run() return, once the callback is done.
+    fn quit(&self);
 }
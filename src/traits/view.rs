@@ -23,36 +23,41 @@ pub struct DrawableGeometry {
 // FIXME: why not Servo events again?
 
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ViewEvent {
     GeometryDidChange,
     MouseWheel(MouseScrollDelta, TouchPhase),
+    // Touchpad pinch. Factor relative to the previous event.
+    Pinch(f32),
     MouseInput(ElementState, MouseButton, i32, i32),
     MouseMoved(i32, i32),
+    // Finger id, phase, position
+    Touch(u64, TouchPhase, i32, i32),
     KeyEvent(Option<char>, Key, KeyState, KeyModifiers),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TouchPhase {
     Started,
     Moved,
     Ended,
+    Cancelled,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ElementState {
     Pressed,
     Released,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum MouseButton {
     Left,
     Right,
     Middle,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum MouseScrollDelta {
 	LineDelta(f32, f32),
 	PixelDelta(f32, f32)
@@ -19,29 +19,41 @@ pub enum WindowEvent {
     WillClose,
     OptionsClosed,
     UrlbarFocusChanged(bool),
+    // The window gained or lost the keyboard focus
+    WindowFocusChanged(bool),
+    ContextMenuClosed,
     DoCommand(WindowCommand),
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum WindowCommand {
     Reload,
     Stop,
     NavigateBack,
     NavigateForward,
+    // Index in the session history
+    NavigateTo(usize),
     OpenLocation,
     OpenInDefaultBrowser,
     ZoomIn,
     ZoomOut,
     ZoomToActualSize,
     ToggleSidebar,
+    CopyUrl,
+    PasteAndGo,
+    OpenInNewTab(String),
+    CopyLink(String),
     NewTab,
     CloseTab,
+    ReopenClosedTab,
     NextTab,
     PrevTab,
     SelectTab(usize),
     ShowOptions,
     Load(String),
     ToggleOptionShowLogs,
+    ToggleOptionSmoothScrolling,
+    ToggleOptionKineticScrolling,
     ToggleOptionFragmentBorders,
     ToggleOptionParallelDisplayListBuidling,
     ToggleOptionShowParallelLayout,
@@ -58,4 +70,7 @@ pub trait WindowMethods {
     fn new_event_loop_waker(&self) -> Box<EventLoopWaker>;
     fn get_events(&self) -> Vec<WindowEvent>;
     fn append_logs(&self, logs: &Vec<ShellLog>);
+    fn supports_clipboard(&self) -> bool;
+    fn get_clipboard_contents(&self) -> Option<String>;
+    fn set_clipboard_contents(&self, contents: &str);
 }